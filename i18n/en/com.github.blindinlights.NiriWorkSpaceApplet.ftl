@@ -1 +1,11 @@
-
+incompatible-niri = Incompatible niri version: running { $running }, this applet supports { $supported }
+niri-unavailable = Not connected to niri ({ $reason }). Click to retry.
+no-windows = No windows on this workspace
+untitled-window = Untitled window
+rename-workspace = Rename…
+workspace-name = Workspace name
+clear-name = Clear name
+move-to-monitor = Move to monitor
+close-all-windows = Close all windows
+not-connected = Not connected to niri
+actions-unsupported = niri { $running } is too old for this applet, which needs { $supported }
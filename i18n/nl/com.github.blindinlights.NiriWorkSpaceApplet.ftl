@@ -1 +1,11 @@
-
+incompatible-niri = Incompatibele niri-versie: { $running } draait, deze applet ondersteunt { $supported }
+niri-unavailable = Niet verbonden met niri ({ $reason }). Klik om opnieuw te proberen.
+no-windows = Geen vensters op deze werkruimte
+untitled-window = Naamloos venster
+rename-workspace = Hernoemen…
+workspace-name = Naam van de werkruimte
+clear-name = Naam wissen
+move-to-monitor = Naar monitor verplaatsen
+close-all-windows = Alle vensters sluiten
+not-connected = Niet verbonden met niri
+actions-unsupported = niri { $running } is te oud voor deze applet, die { $supported } nodig heeft
@@ -1,40 +1,150 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+use std::sync::{Arc, LazyLock};
 use std::time::{Duration, Instant};
 
 use cosmic::app::{Core, Task};
 use cosmic::applet::cosmic_panel_config::PanelAnchor;
+use cosmic::applet::{menu_button, padded_control};
 use cosmic::iced::event::listen_with;
+use cosmic::iced::futures::{stream, StreamExt};
+use cosmic::iced::keyboard::{self, Modifiers};
 use cosmic::iced::mouse::{self, ScrollDelta};
-use cosmic::iced::{Alignment, Background, Border, Length, Limits, Subscription};
-use cosmic::iced_widget::{button, column, row};
-use cosmic::widget::{autosize, container, horizontal_space, vertical_space};
+use cosmic::iced::platform_specific::shell::commands::popup::{destroy_popup, get_popup};
+use cosmic::iced::{touch, window};
+use cosmic::iced::{Alignment, Background, Border, Color, Length, Limits, Subscription};
+use cosmic::iced_widget::{button, column, mouse_area, row};
+use cosmic::widget::{
+    autosize, container, divider, dnd_destination::dnd_destination_for_data, dnd_source,
+    horizontal_space, icon, text, text_input, tooltip, vertical_space,
+};
 use cosmic::{Application, Element, Theme};
-use log::debug;
-use niri_ipc::socket::Socket;
-use niri_ipc::Workspace;
+use log::{debug, error, warn};
+use niri_ipc::{Action, Window, Workspace, WorkspaceReferenceArg};
 
-use crate::niri::{self, NiriSocketExt, WorkspaceUpdate};
+use crate::backend::WorkspaceBackend;
+use crate::config::{
+    BindingModifiers, ClickAction, ClickButton, Config, HideEmpty, HorizontalScroll, OutputFilter,
+    OutputLabel, UrgentColor, WindowIndicator,
+};
+use crate::dnd::Dragged;
+use crate::fl;
+use crate::icons::{self, AppIcon};
+use crate::niri::{Compatibility, NiriState, WorkspaceUpdate, NIRI_IPC_VERSION};
+use crate::scroll::ScrollAccumulator;
 
-// use crate::fl;
 pub struct NiriWorkspaceApplet {
     core: Core,
-    workspaces: Vec<Workspace>,
-    focused: u64,
-    socket: Socket,
-    last_scroll: Instant,
+    backend: Arc<dyn WorkspaceBackend>,
+    config: Config,
+    state: NiriState,
+    compatibility: Compatibility,
+    scroll: ScrollAccumulator,
+    connection: Connection,
+    /// Bumped to restart the event subscription on retry.
+    generation: u64,
+    action_error: Option<String>,
+    /// The output group under the pointer, in the grouped view.
+    hovered_output: Option<String>,
+    /// When the last press started, to tell long presses from clicks.
+    pressed_at: Option<Instant>,
+    popup: Option<Popup>,
+    /// A workspace dragged to a new position, highlighted once niri moves it.
+    settling: Option<Settling>,
+    /// Start of the urgency pulse cycle.
+    epoch: Instant,
+    modifiers: Modifiers,
 }
+
+/// A reordered workspace and the index it was dropped at.
+struct Settling {
+    workspace: u64,
+    idx: u8,
+    /// When the drop was sent to niri.
+    dropped: Instant,
+    /// When niri confirmed the move and the highlight started fading.
+    since: Option<Instant>,
+}
+
+/// What the applet starts with.
+pub struct Flags {
+    pub backend: Arc<dyn WorkspaceBackend>,
+    /// The saved settings; later changes arrive through the config watch.
+    pub config: Config,
+}
+
+/// An open popup, and the workspace it is about.
+struct Popup {
+    id: window::Id,
+    workspace: u64,
+    kind: PopupKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PopupKind {
+    WindowList,
+    Menu(Menu),
+}
+
+/// The state of a workspace's context menu.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Menu {
+    /// The name being typed, while renaming.
+    rename: Option<String>,
+    /// Whether the list of other outputs is expanded.
+    monitors: bool,
+}
+
+/// The workspaces of one output, in panel order.
+struct OutputGroup<'a> {
+    output: Option<&'a str>,
+    workspaces: Vec<&'a Workspace>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Connection {
+    /// Waiting for the first snapshot from the event stream.
+    Connecting,
+    Connected,
+    Disconnected {
+        reason: String,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     WorkspaceUpdated(WorkspaceUpdate),
-    FocusWorkspace(u64),
-    MouseScroll(ScrollDelta),
+    Click(ClickButton, u64),
+    /// A scroll over the given surface.
+    MouseScroll(window::Id, ScrollDelta),
+    ActionResult(Result<(), String>),
+    Retry,
+    UpdateConfig(Config),
+    HoverOutput(Option<String>),
+    NewWorkspace(String),
+    Modifiers(Modifiers),
+    /// A press on the given surface.
+    PointerPressed(window::Id),
+    StartRename,
+    RenameInput(String),
+    Rename,
+    ClearName,
+    ToggleMonitors,
+    MoveToMonitor(String),
+    CloseWindows,
+    FocusWindow(u64),
+    Dropped {
+        data: Option<Dragged>,
+        target: u64,
+    },
+    Frame(Instant),
+    PopupClosed(window::Id),
 }
 
 impl Application for NiriWorkspaceApplet {
     type Executor = cosmic::executor::multi::Executor;
 
-    type Flags = ();
+    type Flags = Flags;
 
     type Message = Message;
 
@@ -47,100 +157,427 @@ impl Application for NiriWorkspaceApplet {
     fn core_mut(&mut self) -> &mut Core {
         &mut self.core
     }
-    fn init(core: Core, _flags: Self::Flags) -> (Self, Task<Self::Message>) {
-        let mut socket = Socket::connect().expect("Failed to connect to niri socket.");
-        let mut workspaces = socket.get_workspace();
-        workspaces.sort_by(|w1, w2| w1.idx.cmp(&w2.idx));
+    fn init(core: Core, Flags { backend, config }: Self::Flags) -> (Self, Task<Self::Message>) {
+        // The event stream's first snapshot fills in the state, so a hung niri
+        // cannot block the panel here.
         let app = NiriWorkspaceApplet {
             core,
-            socket,
-            workspaces,
-            focused: 0,
-            last_scroll: Instant::now(),
+            backend,
+            config,
+            state: NiriState::default(),
+            compatibility: Compatibility::default(),
+            scroll: ScrollAccumulator::default(),
+            connection: Connection::Connecting,
+            generation: 0,
+            action_error: None,
+            hovered_output: None,
+            pressed_at: None,
+            popup: None,
+            settling: None,
+            epoch: Instant::now(),
+            modifiers: Modifiers::empty(),
         };
+        if app.config.window_indicator == WindowIndicator::Icons {
+            icons::preload();
+        }
         debug!("App init");
         (app, Task::none())
     }
 
     fn view(&self) -> Element<Self::Message> {
-        if self.workspaces.is_empty() {
+        if let Connection::Disconnected { reason } = &self.connection {
+            return self.status_view(
+                fl!("niri-unavailable", reason = reason.as_str()),
+                Some(Message::Retry),
+            );
+        }
+        if let Compatibility::Incompatible { running } = &self.compatibility {
+            return self.status_view(
+                fl!(
+                    "incompatible-niri",
+                    running = running.as_str(),
+                    supported = NIRI_IPC_VERSION
+                ),
+                None,
+            );
+        }
+        let groups = self.visible_groups();
+        if groups.is_empty() {
             return row![].padding(8).into();
         }
-        let horizontal = matches!(
-            self.core.applet.anchor,
-            PanelAnchor::Top | PanelAnchor::Bottom
-        );
-        let suggested_total = self.core.applet.suggested_size(false).0
-            + self.core.applet.suggested_padding(false) * 2;
-        let suggested_window_size = self.core.applet.suggested_window_size();
 
-        let buttons = self.workspaces.iter().filter_map(|w| {
-            let content = self
-                .core
-                .applet
-                .text(w.name.clone().unwrap_or(w.idx.to_string()))
-                .font(cosmic::font::bold());
-            let (width, height) = if self.core.applet.is_horizontal() {
-                (suggested_total as f32, suggested_window_size.1.get() as f32)
-            } else {
-                (suggested_window_size.0.get() as f32, suggested_total as f32)
-            };
-            let content = row!(content, vertical_space().height(Length::Fixed(height)))
+        // A single output needs neither labels nor hover tracking.
+        let grouped = groups.len() > 1;
+        let focused_output = self
+            .state
+            .focused_workspace()
+            .and_then(|w| w.output.as_deref());
+        let mut sections: Vec<Element<_>> = Vec::with_capacity(groups.len() * 2);
+        for (i, group) in groups.into_iter().enumerate() {
+            if i > 0 {
+                sections.push(divider::vertical::default().into());
+            }
+            let label: Option<Element<_>> = grouped
+                .then(|| self.output_label(group.output))
+                .flatten()
+                .map(|label| self.core.applet.text(label).into());
+            let buttons = group
+                .workspaces
+                .into_iter()
+                .map(|w| self.workspace_button(w));
+            let new_workspace = group
+                .output
+                .filter(|_| self.config.new_workspace_button)
+                .map(|output| {
+                    Element::from(
+                        self.core
+                            .applet
+                            .icon_button("list-add-symbolic")
+                            .on_press(Message::NewWorkspace(output.to_owned())),
+                    )
+                });
+            let section = row(label.into_iter().chain(buttons).chain(new_workspace))
+                .spacing(4)
                 .align_y(Alignment::Center);
+            let mut section = container(section).padding([0, 2]);
+            // The focused output's group is tinted in the accent color.
+            if grouped && group.output == focused_output {
+                section = section.class(focused_group_class());
+            }
+            sections.push(if grouped {
+                mouse_area(section)
+                    .on_enter(Message::HoverOutput(group.output.map(str::to_owned)))
+                    .on_exit(Message::HoverOutput(None))
+                    .into()
+            } else {
+                section.into()
+            });
+        }
+        let layout_section: Element<_> = row(sections).spacing(4).into();
 
-            let content = column!(content, horizontal_space().width(Length::Fixed(width)))
-                .align_x(Alignment::Center);
+        let layout_section = match &self.action_error {
+            Some(e) => tooltip(layout_section, text(e.clone()), tooltip::Position::Bottom).into(),
+            None => layout_section,
+        };
 
-            let btn = button(
-                container(content)
-                    .align_x(Alignment::Center)
-                    .align_y(Alignment::Center),
-            )
-            .padding(if horizontal {
-                [0, self.core.applet.suggested_padding(true)]
-            } else {
-                [self.core.applet.suggested_padding(true), 0]
-            })
-            .on_press(Message::FocusWorkspace(w.id))
-            .padding(2);
-            Some(
-                btn.class(if w.is_focused {
-                    cosmic::theme::iced::Button::Primary
-                } else {
-                    let appearance = |theme: &Theme| {
-                        let cosmic = theme.cosmic();
-                        button::Style {
-                            background: None,
-                            border: Border {
-                                radius: cosmic.radius_xl().into(),
-                                ..Default::default()
-                            },
-                            border_radius: cosmic.radius_xl().into(),
-                            text_color: theme.current_container().component.on.into(),
-                            ..button::Style::default()
-                        }
+        self.autosize(layout_section)
+    }
+
+    fn update(&mut self, message: Self::Message) -> Task<Self::Message> {
+        match message {
+            Message::WorkspaceUpdated(update) => match update {
+                WorkspaceUpdate::Snapshot(state) => {
+                    self.compatibility = check_compatibility(&state);
+                    self.state = state;
+                    self.connection = Connection::Connected;
+                    self.confirm_settling();
+                    self.acknowledge_urgency();
+                }
+                WorkspaceUpdate::Event(event) => {
+                    self.state.apply(event);
+                    self.confirm_settling();
+                    self.acknowledge_urgency();
+                }
+                WorkspaceUpdate::Disconnected(reason) => {
+                    debug!("niri event stream disconnected: {}", reason);
+                    self.connection = Connection::Disconnected { reason };
+                }
+                WorkspaceUpdate::Reconnected => {
+                    debug!("niri event stream reconnected");
+                    self.connection = Connection::Connected;
+                }
+            },
+            Message::Click(button, id) => {
+                if button == ClickButton::Left
+                    && self
+                        .pressed_at
+                        .take()
+                        .is_some_and(|at| at.elapsed() >= LONG_PRESS)
+                {
+                    return self.toggle_popup(id, PopupKind::WindowList);
+                }
+                let modifiers = BindingModifiers {
+                    shift: self.modifiers.shift(),
+                    ctrl: self.modifiers.control(),
+                    alt: self.modifiers.alt(),
+                    logo: self.modifiers.logo(),
+                };
+                return match self.config.bindings.action(button, modifiers) {
+                    Some(action) => self.run_click_action(action, id),
+                    None => Task::none(),
+                };
+            }
+            Message::MouseScroll(surface, delta) => {
+                // Scrolling the window list or the menu is not for the panel.
+                if !self.is_panel(surface) {
+                    return Task::none();
+                }
+                debug!("scroll {:?}", delta);
+                let settings = &self.config.scroll;
+                let mut steps = self
+                    .scroll
+                    .feed(delta, settings.sensitivity, Instant::now());
+                if settings.natural {
+                    steps.x = -steps.x;
+                    steps.y = -steps.y;
+                }
+                if steps.x != 0 {
+                    let left = steps.x > 0;
+                    let action = match settings.horizontal {
+                        HorizontalScroll::None => return Task::none(),
+                        HorizontalScroll::Monitors if left => Action::FocusMonitorLeft {},
+                        HorizontalScroll::Monitors => Action::FocusMonitorRight {},
+                        HorizontalScroll::Columns if left => Action::FocusColumnLeft {},
+                        HorizontalScroll::Columns => Action::FocusColumnRight {},
                     };
-                    cosmic::theme::iced::Button::Custom(Box::new(
-                        move |theme, status| match status {
-                            button::Status::Active => appearance(theme),
-                            button::Status::Hovered => button::Style {
-                                background: Some(Background::Color(
-                                    theme.current_container().component.hover.into(),
-                                )),
-                                border: Border {
-                                    radius: theme.cosmic().radius_xl().into(),
-                                    ..Default::default()
-                                },
-                                ..appearance(theme)
-                            },
-                            button::Status::Pressed | button::Status::Disabled => appearance(theme),
-                        },
-                    ))
-                })
-                .into(),
-            )
-        });
-        let layout_section: Element<_> = row(buttons).spacing(4).into();
+                    return Task::batch(
+                        (0..steps.x.unsigned_abs())
+                            .map(|_| self.dispatch(action.clone()))
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                let steps = steps.y;
+                if steps == 0 {
+                    return Task::none();
+                }
+                let target = self.scroll_output().and_then(|output| {
+                    self.state
+                        .neighbour(output, steps, settings.wrap, settings.skip_empty)
+                });
+                return match target {
+                    Some(id) => self.dispatch(Action::FocusWorkspace {
+                        reference: WorkspaceReferenceArg::Id(id),
+                    }),
+                    None => Task::none(),
+                };
+            }
+            Message::ActionResult(result) => {
+                // A refused move never settles.
+                if result.is_err() {
+                    self.settling = None;
+                }
+                self.action_error = result.err();
+            }
+            Message::Retry => {
+                debug!("Retrying the niri connection");
+                self.generation += 1;
+            }
+            Message::UpdateConfig(config) => {
+                if config.window_indicator == WindowIndicator::Icons {
+                    icons::preload();
+                }
+                self.config = config;
+            }
+            Message::HoverOutput(output) => {
+                self.hovered_output = output;
+            }
+            Message::NewWorkspace(output) => {
+                let Some(id) = self.state.trailing_empty(&output) else {
+                    return Task::none();
+                };
+                let reference = WorkspaceReferenceArg::Id(id);
+                if self.modifiers.shift() {
+                    return match self.state.focused_window() {
+                        Some(window) => self.dispatch(Action::MoveWindowToWorkspace {
+                            window_id: Some(window.id),
+                            reference,
+                            focus: true,
+                        }),
+                        None => Task::none(),
+                    };
+                }
+                return self.dispatch(Action::FocusWorkspace { reference });
+            }
+            Message::Modifiers(modifiers) => {
+                self.modifiers = modifiers;
+            }
+            Message::PointerPressed(surface) => {
+                if self.is_panel(surface) {
+                    self.pressed_at = Some(Instant::now());
+                }
+            }
+            Message::StartRename => {
+                if let Some((w, menu)) = self.menu_mut() {
+                    menu.rename = Some(w.unwrap_or_default());
+                    return text_input::focus(RENAME_INPUT.clone());
+                }
+            }
+            Message::RenameInput(name) => {
+                if let Some((_, menu)) = self.menu_mut() {
+                    menu.rename = Some(name);
+                }
+            }
+            Message::Rename => {
+                let Some(popup) = &self.popup else {
+                    return Task::none();
+                };
+                let PopupKind::Menu(Menu {
+                    rename: Some(name), ..
+                }) = &popup.kind
+                else {
+                    return Task::none();
+                };
+                let name = name.trim().to_owned();
+                let reference = Some(WorkspaceReferenceArg::Id(popup.workspace));
+                let action = if name.is_empty() {
+                    Action::UnsetWorkspaceName { reference }
+                } else {
+                    Action::SetWorkspaceName {
+                        name,
+                        workspace: reference,
+                    }
+                };
+                return Task::batch([self.dispatch(action), self.close_popup()]);
+            }
+            Message::ClearName => {
+                let Some(workspace) = self.popup.as_ref().map(|p| p.workspace) else {
+                    return Task::none();
+                };
+                return Task::batch([
+                    self.dispatch(Action::UnsetWorkspaceName {
+                        reference: Some(WorkspaceReferenceArg::Id(workspace)),
+                    }),
+                    self.close_popup(),
+                ]);
+            }
+            Message::ToggleMonitors => {
+                if let Some((_, menu)) = self.menu_mut() {
+                    menu.monitors = !menu.monitors;
+                }
+            }
+            Message::MoveToMonitor(output) => {
+                let Some(workspace) = self.popup.as_ref().map(|p| p.workspace) else {
+                    return Task::none();
+                };
+                return Task::batch([
+                    self.dispatch(Action::MoveWorkspaceToMonitor {
+                        output,
+                        reference: Some(WorkspaceReferenceArg::Id(workspace)),
+                    }),
+                    self.close_popup(),
+                ]);
+            }
+            Message::CloseWindows => {
+                let Some(workspace) = self.popup.as_ref().map(|p| p.workspace) else {
+                    return Task::none();
+                };
+                return Task::batch([self.close_windows(workspace), self.close_popup()]);
+            }
+            Message::FocusWindow(id) => {
+                return Task::batch([
+                    self.close_popup(),
+                    self.dispatch(Action::FocusWindow { id }),
+                ]);
+            }
+            Message::Dropped {
+                data: Some(Dragged::Window(window)),
+                target,
+            } => {
+                let current = self.state.windows.get(&window).and_then(|w| w.workspace_id);
+                if current != Some(target) {
+                    return self.dispatch(Action::MoveWindowToWorkspace {
+                        window_id: Some(window),
+                        reference: WorkspaceReferenceArg::Id(target),
+                        focus: false,
+                    });
+                }
+            }
+            Message::Dropped {
+                data: Some(Dragged::Workspace(workspace)),
+                target,
+            } => {
+                let (Some(dragged), Some(target)) = (
+                    self.state.workspaces.get(&workspace),
+                    self.state.workspaces.get(&target),
+                ) else {
+                    return Task::none();
+                };
+                // niri reorders workspaces within their output only.
+                if dragged.id == target.id || dragged.output != target.output {
+                    return Task::none();
+                }
+                self.settling = Some(Settling {
+                    workspace,
+                    idx: target.idx,
+                    dropped: Instant::now(),
+                    since: None,
+                });
+                return self.dispatch(Action::MoveWorkspaceToIndex {
+                    index: target.idx.into(),
+                    reference: Some(WorkspaceReferenceArg::Id(workspace)),
+                });
+            }
+            Message::Dropped { data: None, .. } => {}
+            Message::Frame(now) => {
+                if self
+                    .settling
+                    .as_ref()
+                    .and_then(|s| s.since)
+                    .is_some_and(|since| now.duration_since(since) >= SETTLE)
+                {
+                    self.settling = None;
+                }
+            }
+            Message::PopupClosed(id) => {
+                if self.popup.as_ref().is_some_and(|p| p.id == id) {
+                    self.popup = None;
+                }
+            }
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        }
+        Task::none()
+    }
+    fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
+        Subscription::batch([
+            Subscription::run_with_id(("niri-events", self.generation), {
+                // Rebuilt after every update, but only started once per id.
+                let backend = self.backend.clone();
+                stream::once(async move { backend.events() }).flatten()
+            })
+            .map(Message::WorkspaceUpdated),
+            self.core()
+                .watch_config::<Config>(Self::APP_ID)
+                .map(|update| {
+                    for why in update.errors {
+                        error!("Failed to load config: {}", why);
+                    }
+                    Message::UpdateConfig(update.config)
+                }),
+            listen_with(|e, _, surface| input_message(e, surface)),
+            // The short settle fade runs at the frame rate; an urgent
+            // workspace can pulse for hours, so it only redraws on a timer.
+            if matches!(self.settling, Some(Settling { since: Some(_), .. })) {
+                window::frames().map(Message::Frame)
+            } else if self.pulsing() {
+                cosmic::iced::time::every(PULSE_TICK).map(Message::Frame)
+            } else {
+                Subscription::none()
+            },
+        ])
+    }
+
+    fn view_window(&self, id: window::Id) -> Element<Self::Message> {
+        match &self.popup {
+            Some(popup) if popup.id == id => match &popup.kind {
+                PopupKind::WindowList => self.window_list(popup.workspace),
+                PopupKind::Menu(menu) => self.workspace_menu(popup.workspace, menu),
+            },
+            _ => text("").into(),
+        }
+    }
+
+    fn on_close_requested(&self, id: window::Id) -> Option<Self::Message> {
+        Some(Message::PopupClosed(id))
+    }
+
+    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
+        Some(cosmic::applet::style())
+    }
+}
+
+impl NiriWorkspaceApplet {
+    fn autosize<'a>(&self, content: Element<'a, Message>) -> Element<'a, Message> {
         let mut limits = Limits::NONE.min_width(1.).min_height(1.);
         if let Some(b) = self.core.applet.suggested_bounds {
             if b.width as i32 > 0 {
@@ -152,63 +589,1453 @@ impl Application for NiriWorkspaceApplet {
         }
 
         autosize::autosize(
-            container(layout_section).padding(0),
+            container(content).padding(0),
             cosmic::widget::Id::new("autosize-main"),
         )
         .limits(limits)
         .into()
     }
 
-    fn update(&mut self, message: Self::Message) -> Task<Self::Message> {
-        match message {
-            Message::WorkspaceUpdated(update) => match update {
-                WorkspaceUpdate::WorkspaceChanged(workspaces) => {
-                    self.workspaces = workspaces;
-                    self.workspaces.sort_by(|w1, w2| w1.idx.cmp(&w2.idx));
-                }
-                WorkspaceUpdate::FocusChanged(id) => {
-                    self.focused = id;
-                    for w in &mut self.workspaces {
-                        w.is_focused = w.id == id;
-                    }
+    /// A warning icon in place of the workspaces, explained by a tooltip.
+    fn status_view(&self, message: String, on_press: Option<Message>) -> Element<Message> {
+        let icon = self
+            .core
+            .applet
+            .icon_button("dialog-warning-symbolic")
+            .on_press_maybe(on_press);
+        self.autosize(tooltip(icon, text(message), tooltip::Position::Bottom).into())
+    }
+
+    fn workspace_button<'a>(&'a self, w: &'a Workspace) -> Element<'a, Message> {
+        let horizontal = matches!(
+            self.core.applet.anchor,
+            PanelAnchor::Top | PanelAnchor::Bottom
+        );
+        let suggested_total = self.core.applet.suggested_size(false).0
+            + self.core.applet.suggested_padding(false) * 2;
+        let suggested_window_size = self.core.applet.suggested_window_size();
+
+        let windows = self.state.windows_on(w.id);
+        let window_count = windows.len();
+        let label = self
+            .core
+            .applet
+            .text(w.name.clone().unwrap_or(w.idx.to_string()))
+            .font(cosmic::font::bold());
+        let content: Element<_> = match self.config.window_indicator {
+            WindowIndicator::None => label.into(),
+            // Always present, so occupied and empty buttons line up.
+            WindowIndicator::Dots => {
+                let badge = self.core.applet.text(window_badge(window_count)).size(8.0);
+                column!(label, badge).align_x(Alignment::Center).into()
+            }
+            WindowIndicator::Icons if window_count == 0 => label.into(),
+            WindowIndicator::Icons => {
+                let size = (self.core.applet.suggested_size(true).0 / 2).max(12);
+                let (app_icons, overflow) = window_icons(&windows);
+                let app_icons = app_icons
+                    .iter()
+                    .map(|app_icon| Element::from(icon::icon(app_icon.handle()).size(size)));
+                let overflow = (overflow > 0).then(|| {
+                    Element::from(self.core.applet.text(format!("+{}", overflow)).size(8.0))
+                });
+                let app_icons = row(app_icons.chain(overflow))
+                    .spacing(2)
+                    .align_y(Alignment::Center);
+                row!(label, app_icons)
+                    .spacing(4)
+                    .align_y(Alignment::Center)
+                    .into()
+            }
+        };
+        let (width, height) = if self.core.applet.is_horizontal() {
+            (suggested_total as f32, suggested_window_size.1.get() as f32)
+        } else {
+            (suggested_window_size.0.get() as f32, suggested_total as f32)
+        };
+        let content = row!(content, vertical_space().height(Length::Fixed(height)))
+            .align_y(Alignment::Center);
+
+        let content = column!(content, horizontal_space().width(Length::Fixed(width)))
+            .align_x(Alignment::Center);
+
+        let btn = button(
+            container(content)
+                .align_x(Alignment::Center)
+                .align_y(Alignment::Center),
+        )
+        .padding(if horizontal {
+            [0, self.core.applet.suggested_padding(true)]
+        } else {
+            [self.core.applet.suggested_padding(true), 0]
+        })
+        .on_press(Message::Click(ClickButton::Left, w.id))
+        .padding(2)
+        .class(self.button_class(w, window_count == 0));
+        let btn = mouse_area(btn)
+            .on_middle_press(Message::Click(ClickButton::Middle, w.id))
+            .on_right_press(Message::Click(ClickButton::Right, w.id));
+        let target = w.id;
+        let btn = dnd_source(btn)
+            .drag_threshold(8.)
+            .drag_content(move || Dragged::Workspace(target));
+        dnd_destination_for_data(btn, move |data: Option<Dragged>, _| Message::Dropped {
+            data,
+            target,
+        })
+        .into()
+    }
+
+    fn button_class(&self, w: &Workspace, empty: bool) -> cosmic::theme::iced::Button {
+        if w.is_focused {
+            return cosmic::theme::iced::Button::Primary;
+        }
+        let active = w.is_active;
+        let urgent = match self.config.urgent_color {
+            UrgentColor::None => None,
+            _ if !self.state.is_urgent(w.id) => None,
+            color if self.config.urgent_pulse => {
+                let phase = self.epoch.elapsed().as_secs_f32() / PULSE.as_secs_f32();
+                Some((color, 0.6 + 0.4 * (phase * std::f32::consts::TAU).cos()))
+            }
+            color => Some((color, 1.)),
+        };
+        // Fades out over SETTLE once niri has moved a dragged workspace.
+        let settle = self
+            .settling
+            .as_ref()
+            .filter(|s| s.workspace == w.id)
+            .and_then(|s| s.since)
+            .map_or(0., |since| {
+                1. - since.elapsed().as_secs_f32() / SETTLE.as_secs_f32()
+            })
+            .max(0.);
+        let appearance = move |theme: &Theme| {
+            let cosmic = theme.cosmic();
+            // Shown on another output: outlined in the accent color.
+            let (color, width) = match urgent {
+                // Wanting attention: outlined and tinted, in the warning or
+                // destructive color.
+                Some((urgent_color, strength)) => {
+                    let mut color: Color = match urgent_color {
+                        UrgentColor::Destructive => cosmic.destructive_color().into(),
+                        _ => cosmic.warning_color().into(),
+                    };
+                    color.a = strength;
+                    (color, 2.)
                 }
+                None if active => (cosmic.accent_color().into(), 1.5),
+                None => (Color::TRANSPARENT, 0.),
+            };
+            let mut text_color: Color = theme.current_container().component.on.into();
+            if empty {
+                text_color.a *= 0.5;
+            }
+            let background = if settle > 0. {
+                let mut accent: Color = cosmic.accent_color().into();
+                accent.a = 0.5 * settle;
+                Some(Background::Color(accent))
+            } else if urgent.is_some() {
+                let mut tint = color;
+                tint.a *= 0.25;
+                Some(Background::Color(tint))
+            } else {
+                None
+            };
+            button::Style {
+                background,
+                border: Border {
+                    radius: cosmic.radius_xl().into(),
+                    color,
+                    width,
+                },
+                border_radius: cosmic.radius_xl().into(),
+                text_color,
+                ..button::Style::default()
+            }
+        };
+        cosmic::theme::iced::Button::Custom(Box::new(move |theme, status| match status {
+            button::Status::Active => appearance(theme),
+            button::Status::Hovered => button::Style {
+                background: Some(Background::Color(
+                    theme.current_container().component.hover.into(),
+                )),
+                ..appearance(theme)
             },
-            Message::FocusWorkspace(idx) => {
-                self.socket.focus_worspace(idx);
-            }
-            Message::MouseScroll(delta) => {
-                if self.last_scroll.elapsed() > Duration::from_millis(200) {
-                    self.last_scroll = Instant::now();
-                    match delta {
-                        ScrollDelta::Lines { x, y } | ScrollDelta::Pixels { x, y } => {
-                            debug!("scroll x:{} y:{}", x, y);
-                            if y > 0. {
-                                self.socket.focus_worspace_up();
-                            } else {
-                                self.socket.focus_worspace_down();
-                            }
-                        }
-                    }
+            button::Status::Pressed | button::Status::Disabled => appearance(theme),
+        }))
+    }
+
+    /// The workspaces this panel instance shows, grouped by output.
+    ///
+    /// Shows only the panel's own output unless configured otherwise, or
+    /// when niri does not know that output, e.g. outside cosmic-panel. With
+    /// several groups, they are ordered by output name. Empty workspaces are
+    /// left out as configured.
+    fn visible_groups(&self) -> Vec<OutputGroup<'_>> {
+        let mut groups = self.output_groups();
+        for group in &mut groups {
+            let last = group.workspaces.last().map(|w| w.id);
+            group.workspaces.retain(|w| {
+                let hidden = match self.config.hide_empty {
+                    HideEmpty::Never => false,
+                    HideEmpty::Trailing => Some(w.id) == last,
+                    HideEmpty::AllButFocused => !w.is_focused,
+                    HideEmpty::All => true,
+                };
+                !(hidden && self.state.is_empty(w.id))
+            });
+        }
+        // Kept for its "+" button even with every workspace hidden.
+        groups.retain(|g| !g.workspaces.is_empty() || self.config.new_workspace_button);
+        groups
+    }
+
+    fn output_groups(&self) -> Vec<OutputGroup<'_>> {
+        let workspaces = self.state.workspaces();
+        let panel_output = self.core.applet.output_name.as_str();
+        if self.config.outputs == OutputFilter::PanelOutput
+            && workspaces
+                .iter()
+                .any(|w| w.output.as_deref() == Some(panel_output))
+        {
+            return vec![OutputGroup {
+                output: Some(panel_output),
+                workspaces: workspaces
+                    .into_iter()
+                    .filter(|w| w.output.as_deref() == Some(panel_output))
+                    .collect(),
+            }];
+        }
+
+        let mut groups: Vec<OutputGroup> = Vec::new();
+        for w in workspaces {
+            match groups.iter_mut().find(|g| g.output == w.output.as_deref()) {
+                Some(group) => group.workspaces.push(w),
+                None => groups.push(OutputGroup {
+                    output: w.output.as_deref(),
+                    workspaces: vec![w],
+                }),
+            }
+        }
+        // Sorted by name so that moving focus does not reorder the panel.
+        groups.sort_by_key(|g| (g.output.is_none(), g.output));
+        groups
+    }
+
+    fn visible_workspaces(&self) -> Vec<&Workspace> {
+        self.visible_groups()
+            .into_iter()
+            .flat_map(|g| g.workspaces)
+            .collect()
+    }
+
+    fn output_label(&self, output: Option<&str>) -> Option<String> {
+        let output = output?;
+        match self.config.output_label {
+            OutputLabel::Hidden => None,
+            OutputLabel::Connector => Some(output.to_owned()),
+            OutputLabel::Model => self
+                .state
+                .outputs
+                .get(output)
+                .map(|o| format!("{} {}", o.make, o.model)),
+        }
+    }
+
+    /// The windows on `workspace`, each focusable with a click.
+    fn window_list(&self, workspace: u64) -> Element<Message> {
+        let windows = self.state.windows_on(workspace);
+        let content: Element<_> = if windows.is_empty() {
+            container(text::body(fl!("no-windows")))
+                .padding([8, 12])
+                .into()
+        } else {
+            let entries = windows.into_iter().map(|window| {
+                let app_id = window.app_id.as_deref().unwrap_or_default();
+                let title = window
+                    .title
+                    .clone()
+                    .filter(|title| !title.is_empty())
+                    .unwrap_or_else(|| fl!("untitled-window"));
+                let app_icon = icons::app_icon(window.app_id.as_deref());
+                let entry = row!(
+                    icon::icon(app_icon.handle()).size(24),
+                    column!(text::body(title), text::caption(app_id.to_owned())),
+                )
+                .spacing(8)
+                .align_y(Alignment::Center);
+                let id = window.id;
+                let entry = menu_button(entry)
+                    .selected(window.is_focused)
+                    .on_press(Message::FocusWindow(id));
+                // Dropped onto a workspace button, the window moves there.
+                Element::from(
+                    dnd_source(entry)
+                        .drag_threshold(8.)
+                        .drag_content(move || Dragged::Window(id)),
+                )
+            });
+            column(entries).padding([8, 0]).into()
+        };
+        self.core.applet.popup_container(content).into()
+    }
+
+    /// The output whose workspaces scrolling switches: the hovered group, else
+    /// the panel's own output, else the focused one when niri does not know
+    /// the panel's.
+    fn scroll_output(&self) -> Option<&str> {
+        // A group that went away under the pointer never reported the exit.
+        if let Some(output) = self.hovered_output.as_deref().filter(|&hovered| {
+            self.visible_groups()
+                .iter()
+                .any(|g| g.output == Some(hovered))
+        }) {
+            return Some(output);
+        }
+        let panel_output = self.core.applet.output_name.as_str();
+        if !self.state.workspaces_on(panel_output).is_empty() {
+            return Some(panel_output);
+        }
+        self.state
+            .focused_workspace()
+            .and_then(|w| w.output.as_deref())
+    }
+
+    /// Whether `surface` is the panel button row rather than a popup.
+    fn is_panel(&self, surface: window::Id) -> bool {
+        self.core
+            .main_window_id()
+            .map_or(true, |main| main == surface)
+    }
+
+    fn run_click_action(&mut self, action: ClickAction, workspace: u64) -> Task<Message> {
+        let reference = WorkspaceReferenceArg::Id(workspace);
+        match action {
+            ClickAction::Focus => self.dispatch(Action::FocusWorkspace { reference }),
+            ClickAction::MoveFocusedWindow => match self.state.focused_window() {
+                Some(window) if window.workspace_id != Some(workspace) => {
+                    self.dispatch(Action::MoveWindowToWorkspace {
+                        window_id: Some(window.id),
+                        reference,
+                        focus: false,
+                    })
                 }
+                _ => Task::none(),
+            },
+            ClickAction::ToggleOverview => self.dispatch(Action::ToggleOverview {}),
+            ClickAction::OpenWindowList => self.toggle_popup(workspace, PopupKind::WindowList),
+            ClickAction::OpenMenu => self.toggle_popup(workspace, PopupKind::Menu(Menu::default())),
+            ClickAction::CloseWindows => self.close_windows(workspace),
+        }
+    }
+
+    fn close_windows(&self, workspace: u64) -> Task<Message> {
+        Task::batch(
+            self.state
+                .windows_on(workspace)
+                .iter()
+                .map(|w| self.dispatch(Action::CloseWindow { id: Some(w.id) }))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Rename, clear the name of, move or empty `workspace`.
+    fn workspace_menu(&self, workspace: u64, menu: &Menu) -> Element<Message> {
+        let Some(w) = self.state.workspaces.get(&workspace) else {
+            return text("").into();
+        };
+        let mut items: Vec<Element<_>> = Vec::new();
+        items.push(match &menu.rename {
+            Some(name) => container(
+                text_input(fl!("workspace-name"), name.as_str())
+                    .id(RENAME_INPUT.clone())
+                    .on_input(Message::RenameInput)
+                    .on_submit(|_| Message::Rename),
+            )
+            .padding([4, 12])
+            .into(),
+            None => menu_button(text::body(fl!("rename-workspace")))
+                .on_press(Message::StartRename)
+                .into(),
+        });
+        items.push(
+            menu_button(text::body(fl!("clear-name")))
+                .on_press_maybe(w.name.is_some().then_some(Message::ClearName))
+                .into(),
+        );
+
+        let mut outputs: Vec<_> = self
+            .state
+            .outputs
+            .iter()
+            .filter(|(name, _)| w.output.as_ref() != Some(*name))
+            .collect();
+        outputs.sort_unstable_by_key(|(name, _)| *name);
+        if !outputs.is_empty() {
+            let arrow = if menu.monitors { "▾" } else { "▸" };
+            items.push(
+                menu_button(row!(
+                    text::body(fl!("move-to-monitor")),
+                    horizontal_space(),
+                    text::body(arrow)
+                ))
+                .on_press(Message::ToggleMonitors)
+                .into(),
+            );
+            if menu.monitors {
+                items.extend(outputs.into_iter().map(|(name, output)| {
+                    let label = format!("{} ({} {})", name, output.make, output.model);
+                    Element::from(
+                        menu_button(row!(
+                            horizontal_space().width(Length::Fixed(16.)),
+                            text::body(label)
+                        ))
+                        .on_press(Message::MoveToMonitor(name.clone())),
+                    )
+                }));
             }
-            #[allow(unreachable_patterns)]
-            _ => unreachable!(),
         }
-        Task::none()
+
+        items.push(padded_control(divider::horizontal::default()).into());
+        items.push(
+            menu_button(text::body(fl!("close-all-windows")))
+                .on_press_maybe(
+                    (!self.state.windows_on(workspace).is_empty()).then_some(Message::CloseWindows),
+                )
+                .into(),
+        );
+        self.core
+            .applet
+            .popup_container(column(items).padding([8, 0]))
+            .into()
     }
-    fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
-        Subscription::batch([
-            niri::sub().map(Message::WorkspaceUpdated),
-            listen_with(|e, _, _| match e {
-                cosmic::iced::Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
-                    Some(Message::MouseScroll(delta))
+
+    /// The current name of the menu's workspace, and the menu, if open.
+    fn menu_mut(&mut self) -> Option<(Option<String>, &mut Menu)> {
+        let popup = self.popup.as_mut()?;
+        let PopupKind::Menu(menu) = &mut popup.kind else {
+            return None;
+        };
+        let name = self
+            .state
+            .workspaces
+            .get(&popup.workspace)
+            .and_then(|w| w.name.clone());
+        Some((name, menu))
+    }
+
+    /// Opens a popup of `kind` for `workspace`, or closes it if already open.
+    fn toggle_popup(&mut self, workspace: u64, kind: PopupKind) -> Task<Message> {
+        let reopen = self.popup.as_ref().map_or(true, |p| {
+            p.workspace != workspace
+                || std::mem::discriminant(&p.kind) != std::mem::discriminant(&kind)
+        });
+        let close = self.close_popup();
+        if !reopen {
+            return close;
+        }
+        if kind == PopupKind::WindowList {
+            icons::preload();
+        }
+        let Some(parent) = self.core.main_window_id() else {
+            return close;
+        };
+        let id = window::Id::unique();
+        self.popup = Some(Popup {
+            id,
+            workspace,
+            kind,
+        });
+        let mut popup_settings = self
+            .core
+            .applet
+            .get_popup_settings(parent, id, None, None, None);
+        popup_settings.positioner.size_limits = Limits::NONE
+            .min_width(200.)
+            .max_width(400.)
+            .min_height(40.)
+            .max_height(600.);
+        Task::batch([close, get_popup(popup_settings)])
+    }
+
+    fn close_popup(&mut self) -> Task<Message> {
+        match self.popup.take() {
+            Some(popup) => destroy_popup(popup.id),
+            None => Task::none(),
+        }
+    }
+
+    /// Clears the urgency of the focused workspace, since the user has seen it.
+    fn acknowledge_urgency(&mut self) {
+        if let Some(id) = self.state.focused_workspace().map(|w| w.id) {
+            self.state.clear_urgency(id);
+        }
+    }
+
+    /// Whether an urgent workspace is shown and should pulse.
+    fn pulsing(&self) -> bool {
+        self.config.urgent_pulse
+            && self.config.urgent_color != UrgentColor::None
+            && self
+                .visible_workspaces()
+                .iter()
+                .any(|w| self.state.is_urgent(w.id))
+    }
+
+    /// Starts the settle highlight once niri reports the dragged workspace
+    /// at its new index, or drops it if the workspace went away or niri
+    /// never moved it.
+    fn confirm_settling(&mut self) {
+        let Some(settling) = &mut self.settling else {
+            return;
+        };
+        match self.state.workspaces.get(&settling.workspace) {
+            Some(w) if w.idx == settling.idx => {
+                settling.since.get_or_insert_with(Instant::now);
+            }
+            Some(_) if settling.dropped.elapsed() < SETTLE_TIMEOUT => {}
+            _ => self.settling = None,
+        }
+    }
+
+    /// Sends `action` to niri, or explains through `ActionResult` why not.
+    fn dispatch(&self, action: Action) -> Task<Message> {
+        let refused = match (&self.connection, &self.compatibility) {
+            (Connection::Connected, Compatibility::Incompatible { running }) => Some(fl!(
+                "actions-unsupported",
+                running = running.as_str(),
+                supported = NIRI_IPC_VERSION
+            )),
+            (Connection::Connected, _) => None,
+            (Connection::Connecting | Connection::Disconnected { .. }, _) => {
+                Some(fl!("not-connected"))
+            }
+        };
+        if let Some(reason) = refused {
+            debug!("Not sending {:?}: {}", action, reason);
+            return cosmic::task::message(Message::ActionResult(Err(reason)));
+        }
+        let reply = self.backend.dispatch(action);
+        cosmic::task::future(async move { Message::ActionResult(reply.await) })
+    }
+}
+
+/// How long a press must be held to open the window list instead.
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+static RENAME_INPUT: LazyLock<cosmic::widget::Id> =
+    LazyLock::new(|| cosmic::widget::Id::new("rename-workspace"));
+
+/// One cycle of the urgency pulse.
+const PULSE: Duration = Duration::from_millis(1200);
+/// How often the pulse is redrawn.
+const PULSE_TICK: Duration = Duration::from_millis(50);
+
+/// How long a reordered workspace stays highlighted.
+const SETTLE: Duration = Duration::from_millis(400);
+/// How long niri gets to move a dropped workspace before the highlight is
+/// given up on.
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most windows shown as dots before switching to a number.
+const MAX_WINDOW_DOTS: usize = 4;
+/// Most application icons per button before a "+N".
+const MAX_WINDOW_ICONS: usize = 3;
+
+/// The message for a raw input event on `surface`, if the applet wants it.
+fn input_message(event: cosmic::iced::Event, surface: window::Id) -> Option<Message> {
+    match event {
+        cosmic::iced::Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+            Some(Message::MouseScroll(surface, delta))
+        }
+        cosmic::iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+        | cosmic::iced::Event::Touch(touch::Event::FingerPressed { .. }) => {
+            Some(Message::PointerPressed(surface))
+        }
+        cosmic::iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+            Some(Message::Modifiers(modifiers))
+        }
+        // Modifier changes only arrive while the panel has keyboard focus, so
+        // a key released elsewhere would otherwise stay held for every click.
+        cosmic::iced::Event::Mouse(mouse::Event::CursorLeft)
+        | cosmic::iced::Event::Window(window::Event::Unfocused) => {
+            Some(Message::Modifiers(Modifiers::empty()))
+        }
+        _ => None,
+    }
+}
+
+/// The tint behind the focused output's group in the grouped view.
+fn focused_group_class() -> cosmic::theme::Container<'static> {
+    cosmic::theme::Container::custom(|theme| {
+        let cosmic = theme.cosmic();
+        let mut tint: Color = cosmic.accent_color().into();
+        tint.a = 0.15;
+        cosmic::iced_widget::container::Style {
+            background: Some(Background::Color(tint)),
+            border: Border {
+                radius: cosmic.radius_xl().into(),
+                ..Border::default()
+            },
+            ..cosmic::iced_widget::container::Style::default()
+        }
+    })
+}
+
+fn window_badge(count: usize) -> String {
+    match count {
+        0 => " ".to_owned(),
+        1..=MAX_WINDOW_DOTS => "•".repeat(count),
+        _ => count.to_string(),
+    }
+}
+
+/// The icons shown for `windows` on a button, and how many windows are left
+/// over for the "+N".
+fn window_icons(windows: &[&Window]) -> (Vec<AppIcon>, usize) {
+    let app_icons = windows
+        .iter()
+        .take(MAX_WINDOW_ICONS)
+        .map(|window| icons::app_icon(window.app_id.as_deref()))
+        .collect();
+    (app_icons, windows.len().saturating_sub(MAX_WINDOW_ICONS))
+}
+
+fn check_compatibility(state: &NiriState) -> Compatibility {
+    let compatibility = state.compatibility();
+    match &compatibility {
+        Compatibility::Unknown => warn!("Could not determine the niri version"),
+        Compatibility::Newer { running } => warn!(
+            "niri {} is newer than niri-ipc {}, some events will be skipped",
+            running, NIRI_IPC_VERSION
+        ),
+        Compatibility::Incompatible { running } => warn!(
+            "niri {} is older than niri-ipc {}, the applet is disabled",
+            running, NIRI_IPC_VERSION
+        ),
+        Compatibility::Compatible => {}
+    }
+    compatibility
+}
+
+#[cfg(test)]
+mod tests {
+    use niri_ipc::Event;
+
+    use super::*;
+    use crate::backend::fake::{window, workspace, FakeBackend};
+    use crate::config::{Binding, Bindings, Scroll};
+
+    /// An applet on `backend` with the default settings, whatever the
+    /// developer's own configuration, past the backend's initial update.
+    fn init(backend: Arc<FakeBackend>) -> NiriWorkspaceApplet {
+        let initial = backend.initial();
+        let flags = Flags {
+            backend,
+            config: Config::default(),
+        };
+        let mut core = Core::default();
+        core.set_main_window_id(Some(window::Id::unique()));
+        let mut app = NiriWorkspaceApplet::init(core, flags).0;
+        let _ = app.update(Message::WorkspaceUpdated(initial));
+        app
+    }
+
+    /// The panel surface, for input events.
+    fn panel(app: &NiriWorkspaceApplet) -> window::Id {
+        app.core.main_window_id().unwrap()
+    }
+
+    fn applet(workspaces: Vec<Workspace>) -> (NiriWorkspaceApplet, Arc<FakeBackend>) {
+        let fake = Arc::new(FakeBackend::new(workspaces));
+        (init(fake.clone()), fake)
+    }
+
+    fn event(app: &mut NiriWorkspaceApplet, event: Event) {
+        let _ = app.update(Message::WorkspaceUpdated(WorkspaceUpdate::Event(event)));
+    }
+
+    /// The messages `task` produces, for tasks that only run futures.
+    async fn run(task: Task<Message>) -> Vec<Message> {
+        let Some(stream) = cosmic::iced_runtime::task::into_stream(task) else {
+            return Vec::new();
+        };
+        stream
+            .filter_map(|action| async move {
+                match action {
+                    cosmic::iced_runtime::Action::Output(cosmic::Action::App(message)) => {
+                        Some(message)
+                    }
+                    _ => None,
                 }
-                _ => None,
-            }),
-        ])
+            })
+            .collect()
+            .await
     }
 
-    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
-        Some(cosmic::applet::style())
+    fn ids(app: &NiriWorkspaceApplet) -> Vec<u64> {
+        app.state.workspaces().iter().map(|w| w.id).collect()
+    }
+
+    #[test]
+    fn init_sorts_workspaces_by_index() {
+        let (app, _) = applet(vec![workspace(7, 2, "DP-1"), workspace(3, 1, "DP-1")]);
+        assert_eq!(ids(&app), [3, 7]);
+    }
+
+    #[test]
+    fn focus_change_moves_the_focused_flag() {
+        let (mut app, _) = applet(vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")]);
+        event(
+            &mut app,
+            Event::WorkspaceActivated {
+                id: 2,
+                focused: true,
+            },
+        );
+        let focused: Vec<bool> = app
+            .state
+            .workspaces()
+            .iter()
+            .map(|w| w.is_focused)
+            .collect();
+        assert_eq!(focused, [false, true]);
+    }
+
+    #[test]
+    fn workspace_change_replaces_and_sorts() {
+        let (mut app, _) = applet(vec![workspace(1, 1, "DP-1")]);
+        event(
+            &mut app,
+            Event::WorkspacesChanged {
+                workspaces: vec![workspace(5, 2, "DP-1"), workspace(4, 1, "DP-1")],
+            },
+        );
+        assert_eq!(ids(&app), [4, 5]);
+    }
+
+    #[test]
+    fn snapshot_replaces_the_whole_state() {
+        let (mut app, _) = applet(vec![workspace(1, 1, "DP-1")]);
+        let _ = app.update(Message::WorkspaceUpdated(WorkspaceUpdate::Snapshot(
+            NiriState::new(vec![workspace(9, 1, "DP-1")], vec![], Default::default()),
+        )));
+        assert_eq!(ids(&app), [9]);
+    }
+
+    #[test]
+    fn panel_shows_only_its_own_output() {
+        let (mut app, _) = applet(vec![
+            workspace(1, 1, "DP-1"),
+            workspace(2, 1, "HDMI-A-1"),
+            workspace(3, 2, "DP-1"),
+        ]);
+        app.core.applet.output_name = "DP-1".to_owned();
+        let visible: Vec<u64> = app.visible_workspaces().iter().map(|w| w.id).collect();
+        assert_eq!(visible, [1, 3]);
+
+        let _ = app.update(Message::UpdateConfig(Config {
+            outputs: OutputFilter::All,
+            ..Config::default()
+        }));
+        assert_eq!(app.visible_workspaces().len(), 3);
+
+        let _ = app.update(Message::UpdateConfig(Config::default()));
+        app.core.applet.output_name = "eDP-1".to_owned();
+        assert_eq!(app.visible_workspaces().len(), 3);
+    }
+
+    #[test]
+    fn all_outputs_view_keeps_groups_in_output_order() {
+        let mut focused = workspace(2, 1, "HDMI-A-1");
+        focused.is_focused = true;
+        focused.is_active = true;
+        let (mut app, _) = applet(vec![
+            workspace(1, 1, "DP-1"),
+            focused,
+            workspace(3, 2, "DP-1"),
+            workspace(4, 2, "HDMI-A-1"),
+        ]);
+        app.config.outputs = OutputFilter::All;
+
+        let groups: Vec<(Option<&str>, Vec<u64>)> = app
+            .visible_groups()
+            .into_iter()
+            .map(|g| (g.output, g.workspaces.iter().map(|w| w.id).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            [(Some("DP-1"), vec![1, 3]), (Some("HDMI-A-1"), vec![2, 4])]
+        );
+        let _ = app.view();
+
+        event(
+            &mut app,
+            Event::WorkspaceActivated {
+                id: 3,
+                focused: true,
+            },
+        );
+        let outputs: Vec<_> = app.visible_groups().iter().map(|g| g.output).collect();
+        assert_eq!(outputs, [Some("DP-1"), Some("HDMI-A-1")]);
+    }
+
+    #[test]
+    fn scrolling_over_a_group_acts_on_its_output() {
+        let mut active = workspace(1, 1, "DP-1");
+        active.is_active = true;
+        let mut other = workspace(4, 1, "HDMI-A-1");
+        other.is_active = true;
+        let (mut app, fake) = applet(vec![
+            active,
+            workspace(3, 2, "DP-1"),
+            other,
+            workspace(5, 2, "HDMI-A-1"),
+        ]);
+        app.core.applet.output_name = "HDMI-A-1".to_owned();
+        let down = ScrollDelta::Lines { x: 0., y: -1. };
+        let _ = app.update(Message::UpdateConfig(Config {
+            outputs: OutputFilter::All,
+            ..Config::default()
+        }));
+        let _ = app.update(Message::HoverOutput(Some("DP-1".to_owned())));
+        let _ = app.update(Message::MouseScroll(panel(&app), down));
+
+        // Back to the panel's own output, without an exit from the DP-1 group.
+        let _ = app.update(Message::UpdateConfig(Config::default()));
+        let _ = app.update(Message::MouseScroll(panel(&app), down));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(3)
+                },
+                Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(5)
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn scrolling_wraps_and_inverts_as_configured() {
+        let mut active = workspace(1, 1, "DP-1");
+        active.is_active = true;
+        active.is_focused = true;
+        let (mut app, fake) = applet(vec![active, workspace(2, 2, "DP-1")]);
+        let up = ScrollDelta::Lines { x: 0., y: 1. };
+        let _ = app.update(Message::MouseScroll(panel(&app), up));
+        assert!(fake.actions().is_empty());
+
+        let _ = app.update(Message::UpdateConfig(Config {
+            scroll: Scroll {
+                wrap: true,
+                ..Scroll::default()
+            },
+            ..Config::default()
+        }));
+        let _ = app.update(Message::MouseScroll(panel(&app), up));
+        let _ = app.update(Message::UpdateConfig(Config {
+            scroll: Scroll {
+                natural: true,
+                ..Scroll::default()
+            },
+            ..Config::default()
+        }));
+        let _ = app.update(Message::MouseScroll(panel(&app), up));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2)
+                },
+                Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2)
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn input_on_popups_is_not_panel_input() {
+        let mut focused = workspace(1, 1, "DP-1");
+        focused.is_active = true;
+        focused.is_focused = true;
+        let (mut app, fake) = applet(vec![focused, workspace(2, 2, "DP-1")]);
+        let down = ScrollDelta::Lines { x: 0., y: -1. };
+        let popup = window::Id::unique();
+        let _ = app.update(Message::MouseScroll(popup, down));
+        let _ = app.update(Message::PointerPressed(popup));
+        assert!(fake.actions().is_empty());
+        assert!(app.pressed_at.is_none());
+
+        let _ = app.update(Message::MouseScroll(panel(&app), down));
+        let _ = app.update(Message::PointerPressed(panel(&app)));
+        assert_eq!(fake.actions().len(), 1);
+        assert!(app.pressed_at.is_some());
+    }
+
+    #[test]
+    fn scrolling_acts_on_the_panel_output_not_the_focused_one() {
+        let mut focused = workspace(1, 1, "DP-1");
+        focused.is_active = true;
+        focused.is_focused = true;
+        let mut panel_ws = workspace(3, 1, "HDMI-A-1");
+        panel_ws.is_active = true;
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![
+                focused,
+                workspace(2, 2, "DP-1"),
+                panel_ws,
+                workspace(4, 2, "HDMI-A-1"),
+                workspace(5, 3, "HDMI-A-1"),
+            ],
+            vec![window(50, 5, "foot")],
+            Default::default(),
+        )));
+        let mut app = init(fake.clone());
+        app.core.applet.output_name = "HDMI-A-1".to_owned();
+        let down = ScrollDelta::Lines { x: 0., y: -1. };
+        let _ = app.update(Message::MouseScroll(panel(&app), down));
+        let _ = app.update(Message::UpdateConfig(Config {
+            scroll: Scroll {
+                skip_empty: true,
+                ..Scroll::default()
+            },
+            ..Config::default()
+        }));
+        let _ = app.update(Message::MouseScroll(panel(&app), down));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(4)
+                },
+                Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(5)
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn horizontal_scrolling_moves_between_monitors_or_columns() {
+        let (mut app, fake) = applet(vec![workspace(1, 1, "DP-1")]);
+        let _ = app.update(Message::MouseScroll(
+            panel(&app),
+            ScrollDelta::Lines { x: 2., y: 0. },
+        ));
+        let _ = app.update(Message::UpdateConfig(Config {
+            scroll: Scroll {
+                horizontal: HorizontalScroll::Columns,
+                ..Scroll::default()
+            },
+            ..Config::default()
+        }));
+        let _ = app.update(Message::MouseScroll(
+            panel(&app),
+            ScrollDelta::Lines { x: -1., y: 0. },
+        ));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::FocusMonitorLeft {},
+                Action::FocusMonitorLeft {},
+                Action::FocusColumnRight {},
+            ]
+        ));
+    }
+
+    #[test]
+    fn window_badges_count_windows() {
+        assert_eq!(window_badge(0), " ");
+        assert_eq!(window_badge(3), "•••");
+        assert_eq!(window_badge(7), "7");
+
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")],
+            vec![window(10, 1, "foot"), window(11, 1, "firefox")],
+            Default::default(),
+        )));
+        let app = init(fake);
+        assert_eq!(app.state.windows_on(1).len(), 2);
+        assert!(app.state.windows_on(2).is_empty());
+        let _ = app.view();
+    }
+
+    #[test]
+    fn icon_view_renders_overflowing_workspaces() {
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")],
+            (10..15).map(|id| window(id, 1, "foot")).collect(),
+            Default::default(),
+        )));
+        let mut app = init(fake);
+        app.config.window_indicator = WindowIndicator::Icons;
+        let _ = app.view();
+
+        let mut untagged = window(15, 1, "foot");
+        untagged.app_id = None;
+        let mut windows = app.state.windows_on(1);
+        windows.push(&untagged);
+        let (app_icons, overflow) = window_icons(&windows);
+        assert_eq!(app_icons.len(), MAX_WINDOW_ICONS);
+        assert_eq!(overflow, 3);
+        let fallback = AppIcon::Name(icons::FALLBACK_ICON.to_owned());
+        let (app_icons, overflow) = window_icons(&[&untagged]);
+        assert_eq!(app_icons, [fallback]);
+        assert_eq!(overflow, 0);
+    }
+
+    #[test]
+    fn window_list_focuses_the_chosen_window() {
+        let mut focused = window(11, 1, "firefox");
+        focused.is_focused = true;
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![workspace(1, 1, "DP-1")],
+            vec![window(10, 1, "foot"), focused],
+            Default::default(),
+        )));
+        let mut app = init(fake.clone());
+        let _ = app.window_list(1);
+        let _ = app.window_list(2);
+
+        let _ = app.update(Message::FocusWindow(10));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [Action::FocusWindow { id: 10 }]
+        ));
+    }
+
+    fn open_menu(app: &mut NiriWorkspaceApplet, workspace: u64) {
+        app.popup = Some(Popup {
+            id: window::Id::unique(),
+            workspace,
+            kind: PopupKind::Menu(Menu::default()),
+        });
+    }
+
+    #[test]
+    fn menu_renames_and_clears_the_workspace_name() {
+        let (mut app, fake) = applet(vec![workspace(1, 1, "DP-1")]);
+        open_menu(&mut app, 1);
+        let _ = app.workspace_menu(1, &Menu::default());
+        let _ = app.update(Message::StartRename);
+        let _ = app.update(Message::RenameInput(" mail ".to_owned()));
+        let _ = app.update(Message::Rename);
+        assert!(app.popup.is_none());
+
+        open_menu(&mut app, 1);
+        let _ = app.update(Message::ClearName);
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::SetWorkspaceName {
+                    name,
+                    workspace: Some(WorkspaceReferenceArg::Id(1)),
+                },
+                Action::UnsetWorkspaceName {
+                    reference: Some(WorkspaceReferenceArg::Id(1)),
+                },
+            ] if name == "mail"
+        ));
+    }
+
+    #[test]
+    fn menu_moves_the_workspace_and_closes_its_windows() {
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![workspace(1, 1, "DP-1")],
+            vec![window(10, 1, "foot"), window(11, 1, "firefox")],
+            Default::default(),
+        )));
+        let mut app = init(fake.clone());
+        open_menu(&mut app, 1);
+        let _ = app.update(Message::MoveToMonitor("HDMI-A-1".to_owned()));
+        open_menu(&mut app, 1);
+        let _ = app.update(Message::CloseWindows);
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::MoveWorkspaceToMonitor {
+                    output,
+                    reference: Some(WorkspaceReferenceArg::Id(1)),
+                },
+                Action::CloseWindow { id: Some(10) },
+                Action::CloseWindow { id: Some(11) },
+            ] if output == "HDMI-A-1"
+        ));
+    }
+
+    #[test]
+    fn dropping_a_window_moves_it_without_focus() {
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")],
+            vec![window(10, 1, "foot")],
+            Default::default(),
+        )));
+        let mut app = init(fake.clone());
+        let _ = app.update(Message::Dropped {
+            data: Some(Dragged::Window(10)),
+            target: 1,
+        });
+        assert!(fake.actions().is_empty());
+
+        let _ = app.update(Message::Dropped {
+            data: Some(Dragged::Window(10)),
+            target: 2,
+        });
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [Action::MoveWindowToWorkspace {
+                window_id: Some(10),
+                reference: WorkspaceReferenceArg::Id(2),
+                focus: false,
+            }]
+        ));
+    }
+
+    #[test]
+    fn dropping_a_workspace_reorders_it_within_its_output() {
+        let (mut app, fake) = applet(vec![
+            workspace(1, 1, "DP-1"),
+            workspace(2, 2, "DP-1"),
+            workspace(3, 3, "DP-1"),
+            workspace(4, 1, "HDMI-A-1"),
+        ]);
+        let _ = app.update(Message::Dropped {
+            data: Some(Dragged::Workspace(1)),
+            target: 4,
+        });
+        assert!(fake.actions().is_empty());
+
+        let _ = app.update(Message::Dropped {
+            data: Some(Dragged::Workspace(1)),
+            target: 3,
+        });
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [Action::MoveWorkspaceToIndex {
+                index: 3,
+                reference: Some(WorkspaceReferenceArg::Id(1)),
+            }]
+        ));
+        assert!(app.settling.as_ref().is_some_and(|s| s.since.is_none()));
+
+        let mut moved = vec![
+            workspace(2, 1, "DP-1"),
+            workspace(3, 2, "DP-1"),
+            workspace(1, 3, "DP-1"),
+            workspace(4, 1, "HDMI-A-1"),
+        ];
+        moved.sort_by_key(|w| w.id);
+        event(&mut app, Event::WorkspacesChanged { workspaces: moved });
+        assert_eq!(ids(&app), [2, 4, 3, 1]);
+        let since = app.settling.as_ref().and_then(|s| s.since).unwrap();
+
+        let _ = app.update(Message::Frame(since + SETTLE));
+        assert!(app.settling.is_none());
+    }
+
+    #[test]
+    fn refused_or_ignored_drops_do_not_settle() {
+        let (mut app, _) = applet(vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")]);
+        let _ = app.update(Message::Dropped {
+            data: Some(Dragged::Workspace(1)),
+            target: 2,
+        });
+        assert!(app.settling.is_some());
+        let _ = app.update(Message::ActionResult(Err("no".to_owned())));
+        assert!(app.settling.is_none());
+
+        let _ = app.update(Message::Dropped {
+            data: Some(Dragged::Workspace(1)),
+            target: 2,
+        });
+        app.settling.as_mut().unwrap().dropped -= SETTLE_TIMEOUT;
+        event(
+            &mut app,
+            Event::WorkspaceActivated {
+                id: 2,
+                focused: true,
+            },
+        );
+        assert!(app.settling.is_none());
+    }
+
+    #[test]
+    fn urgency_is_cleared_when_the_workspace_is_focused() {
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")],
+            vec![window(20, 2, "chat")],
+            Default::default(),
+        )));
+        let mut app = init(fake);
+        app.state.workspaces.get_mut(&1).unwrap().is_focused = true;
+        event(
+            &mut app,
+            Event::WindowUrgencyChanged {
+                id: 20,
+                urgent: true,
+            },
+        );
+        assert!(app.state.is_urgent(2));
+
+        event(
+            &mut app,
+            Event::WorkspaceActivated {
+                id: 2,
+                focused: true,
+            },
+        );
+        assert!(!app.state.is_urgent(2));
+    }
+
+    #[test]
+    fn empty_workspaces_are_hidden_as_configured() {
+        let mut focused = workspace(3, 3, "DP-1");
+        focused.is_focused = true;
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![
+                workspace(1, 1, "DP-1"),
+                workspace(2, 2, "DP-1"),
+                focused,
+                workspace(4, 4, "DP-1"),
+            ],
+            vec![window(10, 1, "foot")],
+            Default::default(),
+        )));
+        let mut app = init(fake);
+        let shown = |app: &NiriWorkspaceApplet| -> Vec<u64> {
+            app.visible_workspaces().iter().map(|w| w.id).collect()
+        };
+        let hide = |app: &mut NiriWorkspaceApplet, hide_empty| {
+            let _ = app.update(Message::UpdateConfig(Config {
+                hide_empty,
+                ..Config::default()
+            }));
+        };
+        assert_eq!(shown(&app), [1, 2, 3, 4]);
+        hide(&mut app, HideEmpty::Trailing);
+        assert_eq!(shown(&app), [1, 2, 3]);
+        hide(&mut app, HideEmpty::AllButFocused);
+        assert_eq!(shown(&app), [1, 3]);
+        hide(&mut app, HideEmpty::All);
+        assert_eq!(shown(&app), [1]);
+    }
+
+    #[test]
+    fn new_workspace_focuses_or_moves_to_the_trailing_empty_one() {
+        let mut focused = window(10, 1, "foot");
+        focused.is_focused = true;
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![
+                workspace(1, 1, "DP-1"),
+                workspace(2, 2, "DP-1"),
+                workspace(3, 1, "HDMI-A-1"),
+            ],
+            vec![focused],
+            Default::default(),
+        )));
+        let mut app = init(fake.clone());
+        let _ = app.update(Message::NewWorkspace("DP-1".to_owned()));
+        let _ = app.update(Message::Modifiers(Modifiers::SHIFT));
+        let _ = app.update(Message::NewWorkspace("HDMI-A-1".to_owned()));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2)
+                },
+                Action::MoveWindowToWorkspace {
+                    window_id: Some(10),
+                    reference: WorkspaceReferenceArg::Id(3),
+                    focus: true,
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn long_press_does_not_focus_the_workspace() {
+        let (mut app, fake) = applet(vec![workspace(1, 1, "DP-1")]);
+        app.pressed_at = Some(Instant::now() - LONG_PRESS);
+        let _ = app.update(Message::Click(ClickButton::Left, 1));
+        assert!(fake.actions().is_empty());
+    }
+
+    #[test]
+    fn clicking_a_workspace_dispatches_focus() {
+        let (mut app, fake) = applet(vec![workspace(1, 1, "DP-1")]);
+        let _ = app.update(Message::Click(ClickButton::Left, 1));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [Action::FocusWorkspace {
+                reference: WorkspaceReferenceArg::Id(1)
+            }]
+        ));
+    }
+
+    #[test]
+    fn clicks_run_the_configured_bindings() {
+        let mut focused = window(10, 1, "foot");
+        focused.is_focused = true;
+        let fake = Arc::new(FakeBackend::with_state(NiriState::new(
+            vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")],
+            vec![focused, window(20, 2, "firefox")],
+            Default::default(),
+        )));
+        let mut app = init(fake.clone());
+        let mut bindings = Bindings::default();
+        bindings
+            .0
+            .retain(|b| b.action != ClickAction::OpenWindowList);
+        bindings.0.extend([
+            Binding {
+                modifiers: BindingModifiers {
+                    shift: true,
+                    ..Default::default()
+                },
+                ..Binding::new(ClickButton::Left, ClickAction::MoveFocusedWindow)
+            },
+            Binding::new(ClickButton::Middle, ClickAction::CloseWindows),
+        ]);
+        let _ = app.update(Message::UpdateConfig(Config {
+            bindings,
+            ..Config::default()
+        }));
+
+        let _ = app.update(Message::Click(ClickButton::Middle, 2));
+        let _ = app.update(Message::Modifiers(Modifiers::SHIFT));
+        let _ = app.update(Message::Click(ClickButton::Left, 2));
+        let _ = app.update(Message::Modifiers(Modifiers::SHIFT | Modifiers::CTRL));
+        let _ = app.update(Message::Click(ClickButton::Left, 2));
+        assert!(matches!(
+            fake.actions().as_slice(),
+            [
+                Action::CloseWindow { id: Some(20) },
+                Action::MoveWindowToWorkspace {
+                    window_id: Some(10),
+                    reference: WorkspaceReferenceArg::Id(2),
+                    focus: false,
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn modifiers_are_released_when_the_panel_is_left() {
+        let (mut app, fake) = applet(vec![workspace(1, 1, "DP-1")]);
+        for leave in [
+            cosmic::iced::Event::Mouse(mouse::Event::CursorLeft),
+            cosmic::iced::Event::Window(window::Event::Unfocused),
+        ] {
+            let _ = app.update(Message::Modifiers(Modifiers::SHIFT));
+            let message = input_message(leave, panel(&app)).unwrap();
+            let _ = app.update(message);
+            assert!(app.modifiers.is_empty());
+        }
+        let _ = app.update(Message::Click(ClickButton::Left, 1));
+        assert_eq!(fake.actions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_actions_show_their_error() {
+        let (mut app, fake) = applet(vec![workspace(1, 1, "DP-1")]);
+        fake.fail_actions("niri said no");
+        let task = app.update(Message::Click(ClickButton::Left, 1));
+        for message in run(task).await {
+            let _ = app.update(message);
+        }
+        assert_eq!(fake.actions().len(), 1);
+        assert_eq!(app.action_error.as_deref(), Some("niri said no"));
+    }
+
+    #[test]
+    fn action_results_are_kept_until_the_next_success() {
+        let (mut app, _) = applet(vec![]);
+        let _ = app.update(Message::ActionResult(Err("boom".to_owned())));
+        assert_eq!(app.action_error.as_deref(), Some("boom"));
+        let _ = app.update(Message::ActionResult(Ok(())));
+        assert_eq!(app.action_error, None);
+    }
+
+    #[test]
+    fn connection_state_follows_updates() {
+        let (mut app, _) = applet(vec![]);
+        let _ = app.update(Message::WorkspaceUpdated(WorkspaceUpdate::Disconnected(
+            "gone".to_owned(),
+        )));
+        assert_eq!(
+            app.connection,
+            Connection::Disconnected {
+                reason: "gone".to_owned()
+            }
+        );
+        let _ = app.view();
+        let _ = app.update(Message::WorkspaceUpdated(WorkspaceUpdate::Reconnected));
+        assert_eq!(app.connection, Connection::Connected);
+    }
+
+    #[tokio::test]
+    async fn missing_niri_shows_an_error_instead_of_panicking() {
+        let fake = Arc::new(FakeBackend::new(vec![workspace(1, 1, "DP-1")]));
+        fake.fail_snapshot("NIRI_SOCKET is not set");
+        let mut app = init(fake.clone());
+        assert!(matches!(app.connection, Connection::Disconnected { .. }));
+        let _ = app.view();
+
+        let task = app.update(Message::Click(ClickButton::Left, 1));
+        for message in run(task).await {
+            let _ = app.update(message);
+        }
+        assert!(fake.actions().is_empty());
+        assert!(app.action_error.is_some());
+
+        let _ = app.update(Message::Retry);
+        assert_eq!(app.generation, 1);
+        let _ = app.update(Message::WorkspaceUpdated(WorkspaceUpdate::Snapshot(
+            NiriState::new(vec![workspace(1, 1, "DP-1")], vec![], Default::default()),
+        )));
+        assert_eq!(app.connection, Connection::Connected);
+    }
+
+    #[test]
+    fn incompatible_niri_disables_actions() {
+        let fake = Arc::new(FakeBackend::with_state(NiriState {
+            version: Some("25.02".to_owned()),
+            ..NiriState::new(vec![workspace(1, 1, "DP-1")], vec![], Default::default())
+        }));
+        let mut app = init(fake.clone());
+        assert!(matches!(
+            app.compatibility,
+            Compatibility::Incompatible { .. }
+        ));
+        let _ = app.update(Message::Click(ClickButton::Left, 1));
+        assert!(fake.actions().is_empty());
+        let _ = app.view();
+    }
+
+    #[test]
+    fn view_renders_without_a_compositor() {
+        let (app, _) = applet(vec![]);
+        let _ = app.view();
+        let (app, _) = applet(vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")]);
+        let _ = app.view();
+    }
+
+    #[tokio::test]
+    async fn fake_events_reach_the_stream() {
+        let fake = FakeBackend::new(vec![]);
+        let mut events = fake.events();
+        fake.push(WorkspaceUpdate::Reconnected);
+        assert!(matches!(
+            events.next().await,
+            Some(WorkspaceUpdate::Snapshot(_))
+        ));
+        assert!(matches!(
+            events.next().await,
+            Some(WorkspaceUpdate::Reconnected)
+        ));
     }
 }
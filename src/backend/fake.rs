@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::sync::{Arc, Mutex};
+
+use cosmic::iced::futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    future::{self, BoxFuture},
+    stream::{self, BoxStream},
+    FutureExt, StreamExt,
+};
+use niri_ipc::{Action, Window, Workspace};
+
+use super::WorkspaceBackend;
+use crate::niri::{NiriState, WorkspaceUpdate};
+
+/// An in-memory compositor for tests.
+///
+/// Updates pushed with [`FakeBackend::push`] come out of
+/// [`WorkspaceBackend::events`], and every dispatched action is recorded.
+pub struct FakeBackend {
+    state: NiriState,
+    sender: UnboundedSender<WorkspaceUpdate>,
+    receiver: Mutex<Option<UnboundedReceiver<WorkspaceUpdate>>>,
+    actions: Arc<Mutex<Vec<Action>>>,
+    action_error: Mutex<Option<String>>,
+    snapshot_error: Mutex<Option<String>>,
+}
+
+impl FakeBackend {
+    pub fn new(workspaces: Vec<Workspace>) -> Self {
+        Self::with_state(NiriState::new(workspaces, Vec::new(), Default::default()))
+    }
+
+    pub fn with_state(state: NiriState) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        Self {
+            state,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            actions: Arc::default(),
+            action_error: Mutex::default(),
+            snapshot_error: Mutex::default(),
+        }
+    }
+
+    /// Queues `update` on the event stream.
+    pub fn push(&self, update: WorkspaceUpdate) {
+        self.sender
+            .unbounded_send(update)
+            .expect("fake event stream was dropped");
+    }
+
+    /// Makes every following action fail with `error`.
+    pub fn fail_actions(&self, error: &str) {
+        *self.action_error.lock().unwrap() = Some(error.to_owned());
+    }
+
+    /// Makes the initial snapshot fail with `error`, as if niri were not
+    /// running.
+    pub fn fail_snapshot(&self, error: &str) {
+        *self.snapshot_error.lock().unwrap() = Some(error.to_owned());
+    }
+
+    /// What the event stream starts with.
+    pub fn initial(&self) -> WorkspaceUpdate {
+        match self.snapshot_error.lock().unwrap().clone() {
+            Some(error) => WorkspaceUpdate::Disconnected(error),
+            None => WorkspaceUpdate::Snapshot(self.state.clone()),
+        }
+    }
+
+    /// The actions dispatched so far, in order.
+    pub fn actions(&self) -> Vec<Action> {
+        self.actions.lock().unwrap().clone()
+    }
+}
+
+impl WorkspaceBackend for FakeBackend {
+    fn events(&self) -> BoxStream<'static, WorkspaceUpdate> {
+        match self.receiver.lock().unwrap().take() {
+            Some(receiver) => stream::once(future::ready(self.initial()))
+                .chain(receiver)
+                .boxed(),
+            None => stream::empty().boxed(),
+        }
+    }
+
+    fn dispatch(&self, action: Action) -> BoxFuture<'static, Result<(), String>> {
+        self.actions.lock().unwrap().push(action);
+        let result = match self.action_error.lock().unwrap().clone() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        };
+        future::ready(result).boxed()
+    }
+}
+
+pub fn workspace(id: u64, idx: u8, output: &str) -> Workspace {
+    Workspace {
+        id,
+        idx,
+        name: None,
+        output: Some(output.to_owned()),
+        is_urgent: false,
+        is_active: false,
+        is_focused: false,
+        active_window_id: None,
+    }
+}
+
+pub fn window(id: u64, workspace_id: u64, app_id: &str) -> Window {
+    Window {
+        id,
+        title: Some(format!("{} {}", app_id, id)),
+        app_id: Some(app_id.to_owned()),
+        pid: None,
+        workspace_id: Some(workspace_id),
+        is_focused: false,
+        is_floating: false,
+        is_urgent: false,
+    }
+}
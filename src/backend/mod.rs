@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+#[cfg(test)]
+pub mod fake;
+
+use cosmic::iced::futures::{future::BoxFuture, stream::BoxStream};
+use niri_ipc::Action;
+
+use crate::niri::WorkspaceUpdate;
+
+/// Everything the applet needs from the compositor.
+///
+/// The applet only talks to niri through this trait, so its state handling
+/// can be driven by [`fake::FakeBackend`] in tests.
+pub trait WorkspaceBackend: Send + Sync + 'static {
+    /// Live updates, polled by the applet's subscription.
+    ///
+    /// Starts with a [`WorkspaceUpdate::Snapshot`] of the current state, or
+    /// [`WorkspaceUpdate::Disconnected`] when the compositor is unreachable;
+    /// the applet shows nothing until then.
+    ///
+    /// Called once each time the subscription starts, e.g. on retry.
+    fn events(&self) -> BoxStream<'static, WorkspaceUpdate>;
+
+    /// Runs `action`, resolving to a user-facing error message on failure.
+    fn dispatch(&self, action: Action) -> BoxFuture<'static, Result<(), String>>;
+}
@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, CosmicConfigEntry, PartialEq)]
+#[version = 1]
+pub struct Config {
+    /// Which outputs' workspaces this panel instance shows.
+    pub outputs: OutputFilter,
+    /// What to show before each output's workspaces when showing them all.
+    pub output_label: OutputLabel,
+    /// How each button shows the windows on its workspace.
+    pub window_indicator: WindowIndicator,
+    /// How workspaces with windows that want attention stand out.
+    pub urgent_color: UrgentColor,
+    /// Whether urgent workspaces pulse instead of staying highlighted.
+    pub urgent_pulse: bool,
+    /// Which workspaces without windows are left out.
+    pub hide_empty: HideEmpty,
+    /// Whether each output's workspaces end in a button for its empty
+    /// workspace.
+    pub new_workspace_button: bool,
+    /// What clicking a workspace button does.
+    pub bindings: Bindings,
+    /// How scrolling over the applet switches workspaces.
+    pub scroll: Scroll,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputFilter {
+    /// Only the workspaces of the output the panel is on.
+    #[default]
+    PanelOutput,
+    /// Every workspace of every output.
+    All,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputLabel {
+    #[default]
+    Hidden,
+    /// The connector name, e.g. `DP-1`.
+    Connector,
+    /// The monitor's make and model.
+    Model,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowIndicator {
+    None,
+    /// One dot per window, or a count when there are many.
+    #[default]
+    Dots,
+    /// The windows' application icons.
+    Icons,
+}
+
+impl Config {
+    /// The saved settings of `app_id`, with defaults for anything missing or
+    /// unreadable.
+    pub fn load(app_id: &str) -> Self {
+        cosmic_config::Config::new(app_id, Self::VERSION)
+            .map(|context| match Self::get_entry(&context) {
+                Ok(config) => config,
+                Err((errors, config)) => {
+                    for why in errors {
+                        error!("Failed to load config: {}", why);
+                    }
+                    config
+                }
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scroll {
+    /// Multiplies scroll distances, higher switches sooner.
+    pub sensitivity: f32,
+    /// Scrolling down moves up the workspace list, like touchpad content.
+    pub natural: bool,
+    /// Scrolling past the last workspace continues at the first.
+    pub wrap: bool,
+    /// What scrolling sideways moves between.
+    pub horizontal: HorizontalScroll,
+    /// Scrolling passes over workspaces without windows.
+    pub skip_empty: bool,
+}
+
+impl Default for Scroll {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.,
+            natural: false,
+            wrap: false,
+            horizontal: HorizontalScroll::default(),
+            skip_empty: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HorizontalScroll {
+    /// Sideways scrolling is ignored.
+    None,
+    /// Focus the monitor to the left or right.
+    #[default]
+    Monitors,
+    /// Focus the column to the left or right on the current workspace.
+    Columns,
+}
+
+/// Clicks on a workspace button and what they do, first match wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bindings(pub Vec<Binding>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self(vec![
+            Binding::new(ClickButton::Left, ClickAction::Focus),
+            Binding::new(ClickButton::Middle, ClickAction::OpenWindowList),
+            Binding::new(ClickButton::Right, ClickAction::OpenMenu),
+        ])
+    }
+}
+
+impl Bindings {
+    /// The action bound to `button` with exactly `modifiers` held.
+    pub fn action(&self, button: ClickButton, modifiers: BindingModifiers) -> Option<ClickAction> {
+        self.0
+            .iter()
+            .find(|b| b.button == button && b.modifiers == modifiers)
+            .map(|b| b.action)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub button: ClickButton,
+    #[serde(default)]
+    pub modifiers: BindingModifiers,
+    pub action: ClickAction,
+}
+
+impl Binding {
+    pub fn new(button: ClickButton, action: ClickAction) -> Self {
+        Self {
+            button,
+            modifiers: BindingModifiers::default(),
+            action,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClickButton {
+    Left,
+    Middle,
+    Right,
+}
+
+/// The modifiers a binding needs held, all others released.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BindingModifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    #[serde(rename = "super")]
+    pub logo: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClickAction {
+    /// Focus the workspace.
+    Focus,
+    /// Move the focused window to the workspace.
+    MoveFocusedWindow,
+    ToggleOverview,
+    OpenWindowList,
+    /// Open the rename and move menu.
+    OpenMenu,
+    /// Close every window on the workspace.
+    CloseWindows,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HideEmpty {
+    /// Every workspace is shown.
+    #[default]
+    Never,
+    /// Only the empty workspace niri keeps at the end of each output.
+    Trailing,
+    /// Every empty workspace except the focused one.
+    AllButFocused,
+    /// Every empty workspace.
+    All,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UrgentColor {
+    /// Urgent workspaces look like any other.
+    None,
+    /// The theme's warning color.
+    #[default]
+    Warning,
+    /// The theme's destructive color.
+    Destructive,
+}
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Data carried by drag and drop between the window list and the panel.
+
+use std::borrow::Cow;
+
+use cosmic::iced::clipboard::mime::{AllowedMimeTypes, AsMimeTypes};
+
+const WINDOW_MIME: &str = "application/x-niri-window-id";
+const WORKSPACE_MIME: &str = "application/x-niri-workspace-id";
+
+/// A niri window or workspace being dragged, by id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dragged {
+    Window(u64),
+    Workspace(u64),
+}
+
+impl Dragged {
+    fn mime_type(&self) -> &'static str {
+        match self {
+            Dragged::Window(_) => WINDOW_MIME,
+            Dragged::Workspace(_) => WORKSPACE_MIME,
+        }
+    }
+}
+
+impl AsMimeTypes for Dragged {
+    fn available(&self) -> Cow<'static, [String]> {
+        Cow::Owned(vec![self.mime_type().to_owned()])
+    }
+
+    fn as_bytes(&self, mime_type: &str) -> Option<Cow<'static, [u8]>> {
+        let (Dragged::Window(id) | Dragged::Workspace(id)) = self;
+        (mime_type == self.mime_type()).then(|| Cow::Owned(id.to_string().into_bytes()))
+    }
+}
+
+impl AllowedMimeTypes for Dragged {
+    fn allowed() -> Cow<'static, [String]> {
+        Cow::Owned(vec![WINDOW_MIME.to_owned(), WORKSPACE_MIME.to_owned()])
+    }
+}
+
+impl TryFrom<(Vec<u8>, String)> for Dragged {
+    type Error = anyhow::Error;
+
+    fn try_from((data, mime_type): (Vec<u8>, String)) -> Result<Self, Self::Error> {
+        let id = std::str::from_utf8(&data)?.trim().parse()?;
+        match mime_type.as_str() {
+            WINDOW_MIME => Ok(Dragged::Window(id)),
+            WORKSPACE_MIME => Ok(Dragged::Workspace(id)),
+            _ => anyhow::bail!("unexpected mime type {}", mime_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dragged_ids_round_trip() {
+        for dragged in [Dragged::Window(42), Dragged::Workspace(42)] {
+            let mime_type = dragged.mime_type().to_owned();
+            let bytes = dragged.as_bytes(&mime_type).unwrap().into_owned();
+            assert_eq!(Dragged::try_from((bytes, mime_type)).unwrap(), dragged);
+        }
+        assert!(Dragged::Window(42).as_bytes(WORKSPACE_MIME).is_none());
+        assert!(Dragged::try_from((b"42".to_vec(), "text/plain".to_owned())).is_err());
+    }
+}
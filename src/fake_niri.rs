@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A stand-in for niri's IPC socket, for exercising the real client code.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+use niri_ipc::{Action, Event, Output, Reply, Request, Response, Window, Workspace};
+use tempfile::TempDir;
+
+/// `NIRI_SOCKET` is process-wide, so servers that export it run one at a time.
+static SOCKET_ENV: Mutex<()> = Mutex::new(());
+
+#[derive(Default, Clone)]
+pub struct Script {
+    /// Answer to `Request::Workspaces`.
+    pub workspaces: Vec<Workspace>,
+    /// Answer to `Request::Windows`.
+    pub windows: Vec<Window>,
+    /// Answer to `Request::Outputs`.
+    pub outputs: HashMap<String, Output>,
+    /// Answer to `Request::Version`.
+    pub version: String,
+    /// Written verbatim after an `EventStream` request is handled.
+    pub raw_lines: Vec<String>,
+    /// Replayed in order after `raw_lines`.
+    pub events: Vec<Event>,
+    /// Close event streams after the replay instead of keeping them open.
+    pub close_after_replay: bool,
+}
+
+pub struct FakeNiri {
+    _dir: TempDir,
+    path: PathBuf,
+    actions: Arc<Mutex<Vec<Action>>>,
+    stopped: Arc<AtomicBool>,
+    _env: MutexGuard<'static, ()>,
+}
+
+impl FakeNiri {
+    /// Binds a socket in a fresh tempdir and points `NIRI_SOCKET` at it.
+    pub fn start(script: Script) -> Self {
+        let env = SOCKET_ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let path = dir.path().join("niri.sock");
+        let listener = UnixListener::bind(&path).expect("failed to bind fake niri socket");
+        std::env::set_var(niri_ipc::socket::SOCKET_PATH_ENV, &path);
+
+        let actions = Arc::<Mutex<Vec<Action>>>::default();
+        let stopped = Arc::new(AtomicBool::new(false));
+        {
+            let actions = actions.clone();
+            let stopped = stopped.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let script = script.clone();
+                    let actions = actions.clone();
+                    thread::spawn(move || serve(stream, &script, &actions));
+                }
+            });
+        }
+
+        Self {
+            _dir: dir,
+            path,
+            actions,
+            stopped,
+            _env: env,
+        }
+    }
+
+    /// The actions received so far, in order.
+    pub fn actions(&self) -> Vec<Action> {
+        self.actions.lock().unwrap().clone()
+    }
+}
+
+impl Drop for FakeNiri {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag.
+        let _ = UnixStream::connect(&self.path);
+        std::env::remove_var(niri_ipc::socket::SOCKET_PATH_ENV);
+    }
+}
+
+fn serve(stream: UnixStream, script: &Script, actions: &Mutex<Vec<Action>>) {
+    let mut writer = stream
+        .try_clone()
+        .expect("failed to clone fake niri stream");
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    while matches!(reader.read_line(&mut line), Ok(n) if n > 0) {
+        let reply: Reply = match serde_json::from_str::<Request>(line.trim()) {
+            Ok(Request::Workspaces) => Ok(Response::Workspaces(script.workspaces.clone())),
+            Ok(Request::Windows) => Ok(Response::Windows(script.windows.clone())),
+            Ok(Request::Outputs) => Ok(Response::Outputs(script.outputs.clone())),
+            Ok(Request::Version) => Ok(Response::Version(script.version.clone())),
+            Ok(Request::Action(action)) => {
+                actions.lock().unwrap().push(action);
+                Ok(Response::Handled)
+            }
+            Ok(Request::EventStream) => {
+                let handled: Reply = Ok(Response::Handled);
+                if write_line(&mut writer, serde_json::to_string(&handled)).is_err() {
+                    return;
+                }
+                for raw in &script.raw_lines {
+                    if write_line(&mut writer, Ok(raw.clone())).is_err() {
+                        return;
+                    }
+                }
+                for event in &script.events {
+                    if write_line(&mut writer, serde_json::to_string(event)).is_err() {
+                        return;
+                    }
+                }
+                if script.close_after_replay {
+                    return;
+                }
+                line.clear();
+                continue;
+            }
+            Ok(request) => Err(format!("fake niri does not handle {:?}", request)),
+            Err(e) => Err(format!("error parsing request: {}", e)),
+        };
+        if write_line(&mut writer, serde_json::to_string(&reply)).is_err() {
+            return;
+        }
+        line.clear();
+    }
+}
+
+fn write_line(writer: &mut UnixStream, json: serde_json::Result<String>) -> std::io::Result<()> {
+    let mut buf = json?;
+    buf.push('\n');
+    writer.write_all(buf.as_bytes())
+}
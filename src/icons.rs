@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Resolving niri `app_id`s to application icons through desktop entries.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Once};
+
+use cosmic::desktop::{self, fde, IconSource};
+use cosmic::widget::icon::{self, IconFallback};
+
+/// Shown for windows without a matching desktop entry.
+pub const FALLBACK_ICON: &str = "application-x-executable";
+
+/// An application's icon, as its desktop entry names it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppIcon {
+    /// A name to look up in the icon theme.
+    Name(String),
+    /// An image file, as Flatpak exports and AppImages often use.
+    Path(PathBuf),
+}
+
+impl AppIcon {
+    pub fn handle(&self) -> icon::Handle {
+        match self {
+            AppIcon::Name(name) => icon::from_name(name.as_str())
+                .fallback(Some(IconFallback::Names(vec![FALLBACK_ICON.into()])))
+                .handle(),
+            AppIcon::Path(path) => icon::from_path(path.clone()),
+        }
+    }
+}
+
+/// Icons by lowercased desktop id and `StartupWMClass`.
+static DESKTOP_ICONS: LazyLock<HashMap<String, AppIcon>> = LazyLock::new(|| {
+    let locales = fde::get_languages_from_env();
+    let mut icons = HashMap::new();
+    for entry in desktop::load_applications(&locales, true, None) {
+        let icon = match entry.icon {
+            IconSource::Name(name) => AppIcon::Name(name),
+            IconSource::Path(path) => AppIcon::Path(path),
+        };
+        if let Some(wm_class) = &entry.wm_class {
+            icons.insert(wm_class.to_lowercase(), icon.clone());
+        }
+        icons.insert(entry.id.to_lowercase(), icon);
+    }
+    icons
+});
+
+/// Loads the desktop entries in the background, once, so the first render
+/// with icons does not wait for them.
+pub fn preload() {
+    static PRELOAD: Once = Once::new();
+    PRELOAD.call_once(|| {
+        std::thread::spawn(|| LazyLock::force(&DESKTOP_ICONS));
+    });
+}
+
+/// The icon for a window with `app_id`, or the fallback.
+pub fn app_icon(app_id: Option<&str>) -> AppIcon {
+    app_id
+        .and_then(|app_id| DESKTOP_ICONS.get(&app_id.to_lowercase()))
+        .cloned()
+        .unwrap_or_else(|| AppIcon::Name(FALLBACK_ICON.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_app_ids_get_the_fallback_icon() {
+        let fallback = AppIcon::Name(FALLBACK_ICON.to_owned());
+        assert_eq!(app_icon(Some("definitely.not.an.app.id")), fallback);
+        assert_eq!(app_icon(None), fallback);
+    }
+}
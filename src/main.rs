@@ -1,10 +1,30 @@
 // SPDX-License-Identifier: GPL-3.0-only
 mod app;
+mod backend;
+mod config;
 mod core;
+mod dnd;
+#[cfg(test)]
+mod fake_niri;
+mod icons;
 mod niri;
-use app::NiriWorkspaceApplet;
+mod scroll;
+mod trace;
+use std::sync::Arc;
+
+use app::{Flags, NiriWorkspaceApplet};
+use backend::WorkspaceBackend;
+use config::Config;
+use cosmic::Application;
+use niri::NiriBackend;
+use trace::ReplayBackend;
 
 fn main() -> cosmic::iced::Result {
     env_logger::init();
-    cosmic::applet::run::<NiriWorkspaceApplet>(())
+    let backend: Arc<dyn WorkspaceBackend> = match ReplayBackend::from_env() {
+        Some(replay) => Arc::new(replay),
+        None => Arc::new(NiriBackend),
+    };
+    let config = Config::load(NiriWorkspaceApplet::APP_ID);
+    cosmic::applet::run::<NiriWorkspaceApplet>(Flags { backend, config })
 }
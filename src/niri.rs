@@ -1,156 +1,883 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use cosmic::iced::{
-    futures::{SinkExt, Stream},
-    Subscription,
+use cosmic::iced::futures::{
+    future::BoxFuture, stream::BoxStream, FutureExt, SinkExt, Stream, StreamExt,
 };
-use log::{debug, error};
-use niri_ipc::{socket::Socket, Reply, Workspace};
-use tokio::{io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader}, net::{unix::{OwnedReadHalf, OwnedWriteHalf}, UnixStream}};
-
-pub trait NiriSocketExt {
-    fn focus_worspace(&mut self, idx: u64);
-    fn focus_worspace_up(&mut self);
-    fn focus_worspace_down(&mut self);
-    fn get_workspace(&mut self) -> Vec<Workspace>;
-}
-impl NiriSocketExt for Socket {
-    fn focus_worspace(&mut self, id: u64) {
-        self.send(niri_ipc::Request::Action(
-            niri_ipc::Action::FocusWorkspace {
-                reference: niri_ipc::WorkspaceReferenceArg::Id(id),
+use log::{debug, error, warn};
+use niri_ipc::{
+    Action, Event, KeyboardLayouts, Output, Reply, Request, Response, Window, Workspace,
+};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+};
+
+use crate::backend::WorkspaceBackend;
+use crate::trace;
+
+/// The `niri-ipc` release the applet is built against; keep in sync with the
+/// pin in Cargo.toml.
+pub const NIRI_IPC_VERSION: &str = "25.5.1";
+
+/// A niri release, e.g. `25.05` for both niri `25.05.1 (abc123)` and
+/// `niri-ipc` `25.5.1`. Patch releases never change the IPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NiriRelease {
+    pub year: u32,
+    pub month: u32,
+}
+
+impl NiriRelease {
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version
+            .split(|c: char| !c.is_ascii_digit())
+            .map(str::parse::<u32>);
+        let year = parts.next()?.ok()?;
+        let month = parts.next()?.ok()?;
+        Some(Self { year, month })
+    }
+}
+
+/// Whether the running niri speaks the IPC this build understands.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Compatibility {
+    /// The version could not be queried or parsed.
+    #[default]
+    Unknown,
+    Compatible,
+    /// niri is newer than `niri-ipc`; unknown events are skipped.
+    Newer {
+        running: String,
+    },
+    /// niri predates `niri-ipc`, so replies and events may not parse.
+    Incompatible {
+        running: String,
+    },
+}
+
+impl Compatibility {
+    pub fn check(running: Option<&str>) -> Self {
+        let supported = NiriRelease::parse(NIRI_IPC_VERSION).expect("invalid NIRI_IPC_VERSION");
+        let Some(running) = running else {
+            return Self::Unknown;
+        };
+        match NiriRelease::parse(running) {
+            None => Self::Unknown,
+            Some(release) if release == supported => Self::Compatible,
+            Some(release) if release > supported => Self::Newer {
+                running: running.to_owned(),
             },
-        ))
-        .inspect_err(|e| {
-            error!("Failed to focus workspace {} : {}", id, e);
-        })
-        .ok();
+            Some(_) => Self::Incompatible {
+                running: running.to_owned(),
+            },
+        }
     }
 
-    fn focus_worspace_up(&mut self) {
+    /// Whether actions can be sent without risking a garbled reply.
+    pub fn supports_actions(&self) -> bool {
+        !matches!(self, Self::Incompatible { .. })
+    }
+}
 
-        self.send(niri_ipc::Request::Action(
-            niri_ipc::Action::FocusWorkspaceUp {},
-        ))
-        .inspect_err(|e| {
-            error!("Failed to focus workspace up : {}", e);
-        })
-        .ok();
-        debug!("Focus up");
+/// Everything the applet knows about the compositor.
+///
+/// Starts from a [`NiriState::query`] snapshot and follows the event stream
+/// through [`NiriState::apply`], the same way niri's own `EventStreamState`
+/// does. Outputs have no events, so they only change on a new snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct NiriState {
+    pub workspaces: HashMap<u64, Workspace>,
+    pub windows: HashMap<u64, Window>,
+    pub outputs: HashMap<String, Output>,
+    pub keyboard_layouts: Option<KeyboardLayouts>,
+    pub overview_open: bool,
+    /// The `Request::Version` reply taken with the snapshot.
+    pub version: Option<String>,
+}
+
+impl NiriState {
+    pub fn new(
+        workspaces: Vec<Workspace>,
+        windows: Vec<Window>,
+        outputs: HashMap<String, Output>,
+    ) -> Self {
+        Self {
+            workspaces: workspaces.into_iter().map(|w| (w.id, w)).collect(),
+            windows: windows.into_iter().map(|w| (w.id, w)).collect(),
+            outputs,
+            ..Self::default()
+        }
     }
 
-    fn focus_worspace_down(&mut self) {
-        self.send(niri_ipc::Request::Action(
-            niri_ipc::Action::FocusWorkspaceDown {},
-        ))
-        .inspect_err(|e| {
-            error!("Failed to focus workspace down : {}", e);
+    /// Fetches a full snapshot, one connection per request.
+    ///
+    /// Only the version is taken from a niri older than `niri-ipc`, whose
+    /// other replies may not parse.
+    pub async fn query() -> io::Result<Self> {
+        let version = match NiriClient::connect()
+            .await?
+            .request(Request::Version)
+            .await?
+        {
+            Ok(Response::Version(version)) => Some(version),
+            reply => {
+                error!("{}", unexpected_reply("Version", reply));
+                None
+            }
+        };
+        if !Compatibility::check(version.as_deref()).supports_actions() {
+            return Ok(Self {
+                version,
+                ..Self::default()
+            });
+        }
+        let workspaces = match NiriClient::connect()
+            .await?
+            .request(Request::Workspaces)
+            .await?
+        {
+            Ok(Response::Workspaces(workspaces)) => workspaces,
+            reply => return Err(unexpected_reply("Workspaces", reply)),
+        };
+        let windows = match NiriClient::connect()
+            .await?
+            .request(Request::Windows)
+            .await?
+        {
+            Ok(Response::Windows(windows)) => windows,
+            reply => return Err(unexpected_reply("Windows", reply)),
+        };
+        let outputs = match NiriClient::connect()
+            .await?
+            .request(Request::Outputs)
+            .await?
+        {
+            Ok(Response::Outputs(outputs)) => outputs,
+            reply => return Err(unexpected_reply("Outputs", reply)),
+        };
+        Ok(Self {
+            version,
+            ..Self::new(workspaces, windows, outputs)
         })
-        .ok();
     }
 
-    fn get_workspace(&mut self) -> Vec<Workspace> {
-        let res = self.send(niri_ipc::Request::Workspaces).inspect_err(|e| {
-            error!("Failed to get workspace: {}", e);
-        });
-        if let Ok(Ok(response)) = res {
-            match response {
-                niri_ipc::Response::Workspaces(w) => return w,
-                _ => unreachable!(),
+    pub fn compatibility(&self) -> Compatibility {
+        Compatibility::check(self.version.as_deref())
+    }
+
+    pub fn apply(&mut self, event: Event) {
+        match event {
+            Event::WorkspacesChanged { workspaces } => {
+                self.workspaces = workspaces.into_iter().map(|w| (w.id, w)).collect();
+            }
+            Event::WorkspaceUrgencyChanged { id, urgent } => {
+                if let Some(w) = self.workspaces.get_mut(&id) {
+                    w.is_urgent = urgent;
+                }
+            }
+            Event::WorkspaceActivated { id, focused } => {
+                let Some(output) = self.workspaces.get(&id).map(|w| w.output.clone()) else {
+                    return;
+                };
+                for w in self.workspaces.values_mut() {
+                    if w.output == output {
+                        w.is_active = w.id == id;
+                    }
+                    if focused {
+                        w.is_focused = w.id == id;
+                    }
+                }
+            }
+            Event::WorkspaceActiveWindowChanged {
+                workspace_id,
+                active_window_id,
+            } => {
+                if let Some(w) = self.workspaces.get_mut(&workspace_id) {
+                    w.active_window_id = active_window_id;
+                }
             }
+            Event::WindowsChanged { windows } => {
+                self.windows = windows.into_iter().map(|w| (w.id, w)).collect();
+            }
+            Event::WindowOpenedOrChanged { window } => {
+                if window.is_focused {
+                    for w in self.windows.values_mut() {
+                        w.is_focused = false;
+                    }
+                }
+                self.windows.insert(window.id, window);
+            }
+            Event::WindowClosed { id } => {
+                self.windows.remove(&id);
+            }
+            Event::WindowFocusChanged { id } => {
+                for w in self.windows.values_mut() {
+                    w.is_focused = Some(w.id) == id;
+                }
+            }
+            Event::WindowUrgencyChanged { id, urgent } => {
+                if let Some(w) = self.windows.get_mut(&id) {
+                    w.is_urgent = urgent;
+                }
+            }
+            Event::KeyboardLayoutsChanged { keyboard_layouts } => {
+                self.keyboard_layouts = Some(keyboard_layouts);
+            }
+            Event::KeyboardLayoutSwitched { idx } => {
+                if let Some(layouts) = &mut self.keyboard_layouts {
+                    layouts.current_idx = idx;
+                }
+            }
+            Event::OverviewOpenedOrClosed { is_open } => {
+                self.overview_open = is_open;
+            }
+        }
+    }
+
+    /// All workspaces, in panel order.
+    pub fn workspaces(&self) -> Vec<&Workspace> {
+        let mut workspaces: Vec<_> = self.workspaces.values().collect();
+        workspaces.sort_by(|w1, w2| w1.idx.cmp(&w2.idx).then(w1.id.cmp(&w2.id)));
+        workspaces
+    }
+
+    /// The workspaces of `output`, by index.
+    pub fn workspaces_on(&self, output: &str) -> Vec<&Workspace> {
+        let mut workspaces: Vec<_> = self
+            .workspaces
+            .values()
+            .filter(|w| w.output.as_deref() == Some(output))
+            .collect();
+        workspaces.sort_by_key(|w| w.idx);
+        workspaces
+    }
+
+    /// The empty workspace niri keeps at the end of `output`.
+    pub fn trailing_empty(&self, output: &str) -> Option<u64> {
+        let last = self.workspaces_on(output).last()?.id;
+        self.is_empty(last).then_some(last)
+    }
+
+    /// The workspace `steps` above the active one on `output`, or below for
+    /// negative `steps`. Stops at the ends unless `wrap` is set, and passes
+    /// over empty workspaces with `skip_empty`.
+    pub fn neighbour(&self, output: &str, steps: i32, wrap: bool, skip_empty: bool) -> Option<u64> {
+        let mut workspaces = self.workspaces_on(output);
+        if skip_empty {
+            workspaces.retain(|w| w.is_active || !self.is_empty(w.id));
+        }
+        let active = workspaces.iter().position(|w| w.is_active)? as i64;
+        let len = workspaces.len() as i64;
+        let next = active - i64::from(steps);
+        let next = if wrap {
+            next.rem_euclid(len)
         } else {
-            return vec![];
+            next.clamp(0, len - 1)
+        };
+        (next != active).then(|| workspaces[next as usize].id)
+    }
+
+    pub fn focused_workspace(&self) -> Option<&Workspace> {
+        self.workspaces.values().find(|w| w.is_focused)
+    }
+
+    /// The windows on workspace `id`, in a stable order.
+    pub fn windows_on(&self, id: u64) -> Vec<&Window> {
+        let mut windows: Vec<_> = self
+            .windows
+            .values()
+            .filter(|w| w.workspace_id == Some(id))
+            .collect();
+        windows.sort_by_key(|w| w.id);
+        windows
+    }
+
+    pub fn focused_window(&self) -> Option<&Window> {
+        self.windows.values().find(|w| w.is_focused)
+    }
+
+    /// Whether workspace `id` has no windows.
+    pub fn is_empty(&self, id: u64) -> bool {
+        self.workspaces
+            .get(&id)
+            .map_or(true, |w| w.active_window_id.is_none())
+            && self.windows_on(id).is_empty()
+    }
+
+    /// Whether workspace `id` or any window on it wants attention.
+    pub fn is_urgent(&self, id: u64) -> bool {
+        self.workspaces.get(&id).is_some_and(|w| w.is_urgent)
+            || self.windows_on(id).iter().any(|w| w.is_urgent)
+    }
+
+    /// Forgets the urgency of workspace `id` and its windows, until niri
+    /// reports it again.
+    pub fn clear_urgency(&mut self, id: u64) {
+        if let Some(w) = self.workspaces.get_mut(&id) {
+            w.is_urgent = false;
+        }
+        for w in self.windows.values_mut() {
+            if w.workspace_id == Some(id) {
+                w.is_urgent = false;
+            }
         }
     }
 }
 
+fn unexpected_reply(request: &str, reply: Reply) -> io::Error {
+    match reply {
+        Ok(response) => {
+            io::Error::other(format!("unexpected reply to {}: {:?}", request, response))
+        }
+        Err(e) => io::Error::other(e),
+    }
+}
+
 pub struct NiriClient {
     writer: OwnedWriteHalf,
     reader: BufReader<OwnedReadHalf>,
+    skipped_events: u64,
 }
 
 impl NiriClient {
     /// 连接到指定路径的 Niri Unix domain socket。
-    pub async fn connect() ->io::Result<Self> {
-        let socket_path=std::env::var(niri_ipc::socket::SOCKET_PATH_ENV).unwrap();
+    pub async fn connect() -> io::Result<Self> {
+        let socket_path = std::env::var(niri_ipc::socket::SOCKET_PATH_ENV).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: {}", niri_ipc::socket::SOCKET_PATH_ENV, e),
+            )
+        })?;
         let stream = UnixStream::connect(socket_path).await?;
         let (read_half, write_half) = stream.into_split();
         let reader = BufReader::new(read_half);
 
-        Ok(Self { writer: write_half, reader })
+        Ok(Self {
+            writer: write_half,
+            reader,
+            skipped_events: 0,
+        })
     }
 
     /// 发送一个通用的命令并等待回复。
-    pub async fn event_stream(
-        &mut self,
-    ) ->io::Result<Reply> {
-        let request = niri_ipc::Request::EventStream;
+    pub async fn request(&mut self, request: Request) -> io::Result<Reply> {
         let request_json = serde_json::to_string(&request)?;
 
         self.writer.write_all(request_json.as_bytes()).await?;
         self.writer.write_all(b"\n").await?;
         self.writer.flush().await?;
 
-
         let mut reply_line = String::new();
-         self.reader.read_line(&mut reply_line).await? ;
+        if self.reader.read_line(&mut reply_line).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
         let trimmed_reply = reply_line.trim();
 
         let niri_reply: Reply = serde_json::from_str(trimmed_reply)?;
         Ok(niri_reply)
     }
 
-    pub async fn read_event(&mut self) ->io::Result<niri_ipc::Event> {
+    pub async fn event_stream(&mut self) -> io::Result<Reply> {
+        self.request(Request::EventStream).await
+    }
+
+    pub async fn action(&mut self, action: Action) -> io::Result<Reply> {
+        self.request(Request::Action(action)).await
+    }
+
+    /// Reads the next event this build understands.
+    ///
+    /// Lines that do not parse, such as variants added by a newer niri, are
+    /// logged and skipped; only I/O errors end the stream.
+    pub async fn read_event(&mut self) -> io::Result<Event> {
         let mut event_line_buffer = String::new();
-        self.reader.read_line(&mut event_line_buffer).await?;
+        loop {
+            event_line_buffer.clear();
+            if self.reader.read_line(&mut event_line_buffer).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
 
-        let trimmed_line = event_line_buffer.trim();
+            trace::record(&event_line_buffer);
+            let trimmed_line = event_line_buffer.trim();
+            if trimmed_line.is_empty() {
+                continue;
+            }
+
+            match parse_event(trimmed_line) {
+                Ok(event) => return Ok(event),
+                Err(e) => {
+                    self.skipped_events += 1;
+                    warn!("{} ({} skipped so far)", e, self.skipped_events);
+                }
+            }
+        }
+    }
 
-        let event: niri_ipc::Event = serde_json::from_str(trimmed_line)?;
-        Ok(event)
+    /// How many event lines [`NiriClient::read_event`] could not parse.
+    pub fn skipped_events(&self) -> u64 {
+        self.skipped_events
     }
 }
+
+/// Parses one event-stream line, describing why it was rejected otherwise.
+pub fn parse_event(line: &str) -> Result<Event, String> {
+    serde_json::from_str(line).map_err(|e| {
+        // Events are externally tagged, so an unknown variant is an object
+        // with a single key we have never heard of.
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(serde_json::Value::Object(map)) if map.len() == 1 => {
+                let name = map.keys().next().map(String::as_str).unwrap_or_default();
+                format!("Skipping unsupported niri event {}: {}", name, e)
+            }
+            _ => format!("Skipping malformed niri event: {}", e),
+        }
+    })
+}
+
 #[derive(Debug, Clone)]
 pub enum WorkspaceUpdate {
-    WorkspaceChanged(Vec<Workspace>),
-    FocusChanged(u64),
+    /// A full resync, sent whenever the event stream (re)connects.
+    Snapshot(NiriState),
+    /// An event to apply on top of the last snapshot.
+    Event(Event),
+    /// The event stream was lost, or niri was not reachable yet.
+    Disconnected(String),
+    /// The event stream is back after a `Disconnected`.
+    Reconnected,
+}
+
+/// Runs `action` on its own connection, so a stalled compositor never blocks
+/// the UI thread. The error is meant to be shown to the user.
+pub async fn send_action(action: Action) -> Result<(), String> {
+    let description = format!("{:?}", action);
+    let reply = async {
+        let mut client = NiriClient::connect().await?;
+        client.action(action).await
+    }
+    .await;
+    match reply {
+        Ok(Ok(Response::Handled)) => Ok(()),
+        Ok(Ok(response)) => Err(format!(
+            "unexpected reply to {}: {:?}",
+            description, response
+        )),
+        Ok(Err(e)) => Err(format!("{} failed: {}", description, e)),
+        Err(e) => Err(format!("{} failed: {}", description, e)),
+    }
+    .inspect_err(|e| error!("{}", e))
+}
+
+/// The live compositor, reached through `NIRI_SOCKET`.
+pub struct NiriBackend;
+
+impl WorkspaceBackend for NiriBackend {
+    fn events(&self) -> BoxStream<'static, WorkspaceUpdate> {
+        worker().boxed()
+    }
+
+    fn dispatch(&self, action: Action) -> BoxFuture<'static, Result<(), String>> {
+        send_action(action).boxed()
+    }
 }
-pub fn sub() -> Subscription<WorkspaceUpdate> {
-    Subscription::run(worker)
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How long the event stream has to stay up before a drop counts as a fresh
+/// failure rather than part of a crash loop.
+const STABLE_CONNECTION: Duration = Duration::from_secs(5);
+
+/// The wait before the next attempt, after one that kept the event stream up
+/// for `uptime`, or failed to connect at all.
+fn next_backoff(backoff: Duration, uptime: Option<Duration>) -> Duration {
+    match uptime {
+        Some(uptime) if uptime >= STABLE_CONNECTION => INITIAL_BACKOFF,
+        _ => backoff,
+    }
+}
+
+/// Opens the event stream and takes a snapshot on separate connections, so a
+/// fresh (re)connection always starts from a full resync.
+async fn connect_event_stream() -> io::Result<(NiriClient, NiriState)> {
+    let state = NiriState::query().await?;
+
+    let mut client = NiriClient::connect().await?;
+    match client.event_stream().await? {
+        Ok(Response::Handled) => Ok((client, state)),
+        reply => Err(unexpected_reply("EventStream", reply)),
+    }
 }
+
 pub fn worker() -> impl Stream<Item = WorkspaceUpdate> {
     cosmic::iced::stream::channel(4, async |mut output| {
-        let mut niri_socket =
-            NiriClient::connect().await.expect("Event loop :failed to connect to niri socket");
-
-        let reply = niri_socket.event_stream().await.expect("");
-
-        if matches!(reply, Ok(niri_ipc::Response::Handled)) {
-            while let Ok(event) = niri_socket.read_event().await {
-                match event {
-                    niri_ipc::Event::WorkspacesChanged { workspaces } => {
-                        output
-                            .send(WorkspaceUpdate::WorkspaceChanged(workspaces))
-                            .await
-                            .expect("Error send message");
-                    }
-                    niri_ipc::Event::WorkspaceActivated { id, focused } => {
-                        if focused {
-                            output
-                                .send(WorkspaceUpdate::FocusChanged(id))
-                                .await
-                                .inspect_err(|e| {
-                                    error!("{}", e);
-                                })
-                                .unwrap();
-                        }
+        let mut backoff = INITIAL_BACKOFF;
+        // `None` until the first attempt, so startup failures are reported once.
+        let mut connected: Option<bool> = None;
+
+        loop {
+            let (reason, uptime) = match connect_event_stream().await {
+                Ok((mut niri_socket, state)) => {
+                    let up = Instant::now();
+                    if connected == Some(false)
+                        && output.send(WorkspaceUpdate::Reconnected).await.is_err()
+                    {
+                        return;
                     }
-                    _ => {
-                        // debug!("niri event:{:?}",event);
+                    connected = Some(true);
+                    if output.send(WorkspaceUpdate::Snapshot(state)).await.is_err() {
+                        return;
                     }
+
+                    let reason = loop {
+                        let update = match niri_socket.read_event().await {
+                            Ok(event) => WorkspaceUpdate::Event(event),
+                            Err(e) => {
+                                error!(
+                                    "Event loop: lost niri event stream after skipping {} events: {}",
+                                    niri_socket.skipped_events(),
+                                    e
+                                );
+                                break e.to_string();
+                            }
+                        };
+                        if output.send(update).await.is_err() {
+                            return;
+                        }
+                    };
+                    (reason, Some(up.elapsed()))
+                }
+                Err(e) => {
+                    debug!("Event loop: failed to connect to niri socket: {}", e);
+                    (e.to_string(), None)
+                }
+            };
+
+            if connected != Some(false) {
+                connected = Some(false);
+                if output
+                    .send(WorkspaceUpdate::Disconnected(reason))
+                    .await
+                    .is_err()
+                {
+                    return;
                 }
             }
+            backoff = next_backoff(backoff, uptime);
+            debug!("Event loop: reconnecting in {:?}", backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmic::iced::futures::StreamExt;
+    use niri_ipc::WorkspaceReferenceArg;
+
+    use super::*;
+    use crate::backend::fake::{window, workspace};
+    use crate::fake_niri::{FakeNiri, Script};
+
+    async fn next(stream: &mut (impl Stream<Item = WorkspaceUpdate> + Unpin)) -> WorkspaceUpdate {
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("timed out waiting for a workspace update")
+            .expect("worker stream ended")
+    }
+
+    #[test]
+    fn state_follows_workspace_activation() {
+        let mut dp1 = workspace(1, 1, "DP-1");
+        dp1.is_active = true;
+        dp1.is_focused = true;
+        let mut hdmi = workspace(3, 1, "HDMI-A-1");
+        hdmi.is_active = true;
+        let mut state = NiriState::new(
+            vec![dp1, workspace(2, 2, "DP-1"), hdmi],
+            vec![],
+            HashMap::new(),
+        );
+
+        state.apply(Event::WorkspaceActivated {
+            id: 2,
+            focused: false,
+        });
+        assert!(state.workspaces[&2].is_active && !state.workspaces[&2].is_focused);
+        assert!(!state.workspaces[&1].is_active && state.workspaces[&1].is_focused);
+        assert!(state.workspaces[&3].is_active);
+
+        state.apply(Event::WorkspaceActivated {
+            id: 3,
+            focused: true,
+        });
+        assert_eq!(state.focused_workspace().map(|w| w.id), Some(3));
+        assert!(state.workspaces[&2].is_active);
+    }
+
+    #[test]
+    fn state_tracks_windows() {
+        let mut state = NiriState::default();
+        state.apply(Event::WindowsChanged {
+            windows: vec![window(10, 1, "foot"), window(11, 1, "firefox")],
+        });
+        let mut opened = window(12, 2, "foot");
+        opened.is_focused = true;
+        state.apply(Event::WindowOpenedOrChanged { window: opened });
+        state.apply(Event::WindowClosed { id: 10 });
+
+        let ids: Vec<u64> = state.windows_on(1).iter().map(|w| w.id).collect();
+        assert_eq!(ids, [11]);
+        assert_eq!(state.focused_window().map(|w| w.id), Some(12));
+
+        state.apply(Event::WindowFocusChanged { id: Some(11) });
+        assert_eq!(state.focused_window().map(|w| w.id), Some(11));
+        state.apply(Event::WindowUrgencyChanged {
+            id: 12,
+            urgent: true,
+        });
+        assert!(state.windows[&12].is_urgent);
+    }
+
+    #[test]
+    fn state_follows_the_overview() {
+        let mut state = NiriState::default();
+        state.apply(Event::OverviewOpenedOrClosed { is_open: true });
+        assert!(state.overview_open);
+        state.apply(Event::OverviewOpenedOrClosed { is_open: false });
+        assert!(!state.overview_open);
+    }
+
+    #[test]
+    fn neighbours_stop_at_the_ends_unless_wrapping() {
+        let mut active = workspace(1, 1, "DP-1");
+        active.is_active = true;
+        let state = NiriState::new(
+            vec![active, workspace(2, 2, "DP-1"), workspace(3, 3, "DP-1")],
+            vec![],
+            Default::default(),
+        );
+        assert_eq!(state.neighbour("DP-1", -1, false, false), Some(2));
+        assert_eq!(state.neighbour("DP-1", -5, false, false), Some(3));
+        assert_eq!(state.neighbour("DP-1", 1, false, false), None);
+        assert_eq!(state.neighbour("DP-1", 1, true, false), Some(3));
+        assert_eq!(state.neighbour("HDMI-A-1", 1, true, false), None);
+    }
+
+    #[test]
+    fn neighbours_can_skip_empty_workspaces() {
+        let mut active = workspace(1, 1, "DP-1");
+        active.is_active = true;
+        let state = NiriState::new(
+            vec![active, workspace(2, 2, "DP-1"), workspace(3, 3, "DP-1")],
+            vec![window(30, 3, "foot")],
+            Default::default(),
+        );
+        assert_eq!(state.neighbour("DP-1", -1, false, true), Some(3));
+        assert_eq!(state.neighbour("DP-1", -2, false, true), Some(3));
+    }
+
+    #[test]
+    fn urgency_comes_from_the_workspace_or_its_windows() {
+        let mut state = NiriState::new(
+            vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")],
+            vec![window(10, 1, "foot"), window(20, 2, "firefox")],
+            Default::default(),
+        );
+        assert!(!state.is_urgent(1));
+        state.apply(Event::WindowUrgencyChanged {
+            id: 10,
+            urgent: true,
+        });
+        state.apply(Event::WorkspaceUrgencyChanged {
+            id: 2,
+            urgent: true,
+        });
+        assert!(state.is_urgent(1));
+        assert!(state.is_urgent(2));
+
+        state.clear_urgency(1);
+        assert!(!state.is_urgent(1));
+        assert!(state.is_urgent(2));
+    }
+
+    #[test]
+    fn compatibility_compares_releases() {
+        assert_eq!(
+            NiriRelease::parse("25.05.1 (e3f8f3a)"),
+            NiriRelease::parse(NIRI_IPC_VERSION)
+        );
+        assert_eq!(
+            Compatibility::check(Some("25.05.1 (e3f8f3a)")),
+            Compatibility::Compatible
+        );
+        assert!(matches!(
+            Compatibility::check(Some("25.08")),
+            Compatibility::Newer { .. }
+        ));
+        assert!(matches!(
+            Compatibility::check(Some("25.02")),
+            Compatibility::Incompatible { .. }
+        ));
+        assert_eq!(Compatibility::check(Some("git")), Compatibility::Unknown);
+        assert_eq!(Compatibility::check(None), Compatibility::Unknown);
+    }
+
+    #[test]
+    fn ipc_version_matches_the_cargo_pin() {
+        let pin = format!("niri-ipc = \"={}\"", NIRI_IPC_VERSION);
+        assert!(
+            include_str!("../Cargo.toml")
+                .lines()
+                .any(|l| l.trim() == pin),
+            "NIRI_IPC_VERSION does not match the niri-ipc pin in Cargo.toml"
+        );
+    }
+
+    #[test]
+    fn parse_event_explains_rejected_lines() {
+        assert!(parse_event(r#"{"OverviewOpenedOrClosed":{"is_open":true}}"#).is_ok());
+        let unknown = parse_event(r#"{"SomethingNew":{"id":1}}"#).unwrap_err();
+        assert!(unknown.contains("unsupported niri event SomethingNew"));
+        let malformed = parse_event("{not json").unwrap_err();
+        assert!(malformed.contains("malformed"));
+    }
+
+    #[test]
+    fn backoff_resets_only_after_a_stable_connection() {
+        let backoff = Duration::from_secs(8);
+        assert_eq!(next_backoff(backoff, None), backoff);
+        assert_eq!(
+            next_backoff(backoff, Some(Duration::from_millis(10))),
+            backoff
+        );
+        assert_eq!(
+            next_backoff(backoff, Some(STABLE_CONNECTION)),
+            INITIAL_BACKOFF
+        );
+    }
+
+    #[tokio::test]
+    async fn send_action_reaches_niri() {
+        let niri = FakeNiri::start(Script::default());
+        send_action(Action::FocusWorkspace {
+            reference: WorkspaceReferenceArg::Id(3),
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            niri.actions().as_slice(),
+            [Action::FocusWorkspace {
+                reference: WorkspaceReferenceArg::Id(3)
+            }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn worker_forwards_scripted_events() {
+        let _niri = FakeNiri::start(Script {
+            workspaces: vec![workspace(1, 1, "DP-1")],
+            windows: vec![window(10, 1, "foot")],
+            events: vec![
+                Event::WorkspacesChanged {
+                    workspaces: vec![workspace(1, 1, "DP-1"), workspace(2, 2, "DP-1")],
+                },
+                Event::WorkspaceActivated {
+                    id: 2,
+                    focused: true,
+                },
+                Event::WindowClosed { id: 10 },
+            ],
+            ..Script::default()
+        });
+        let mut updates = worker().boxed();
+
+        let WorkspaceUpdate::Snapshot(mut state) = next(&mut updates).await else {
+            panic!("expected a snapshot first");
+        };
+        assert_eq!(state.workspaces.len(), 1);
+        assert_eq!(state.windows.len(), 1);
+        for _ in 0..3 {
+            let WorkspaceUpdate::Event(event) = next(&mut updates).await else {
+                panic!("expected an event");
+            };
+            state.apply(event);
+        }
+        assert_eq!(state.workspaces.len(), 2);
+        assert_eq!(state.focused_workspace().map(|w| w.id), Some(2));
+        assert!(state.windows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn worker_reports_the_version_of_an_older_niri() {
+        let _niri = FakeNiri::start(Script {
+            version: "24.12".to_owned(),
+            workspaces: vec![workspace(1, 1, "DP-1")],
+            ..Script::default()
+        });
+        let mut updates = worker().boxed();
+
+        let WorkspaceUpdate::Snapshot(state) = next(&mut updates).await else {
+            panic!("expected a snapshot first");
+        };
+        assert!(matches!(
+            state.compatibility(),
+            Compatibility::Incompatible { .. }
+        ));
+        assert!(state.workspaces.is_empty());
+    }
+
+    #[tokio::test]
+    async fn worker_skips_events_it_cannot_parse() {
+        let _niri = FakeNiri::start(Script {
+            raw_lines: vec![
+                r#"{"SomethingNew":{"id":1}}"#.to_owned(),
+                "garbage".to_owned(),
+            ],
+            events: vec![Event::OverviewOpenedOrClosed { is_open: true }],
+            ..Script::default()
+        });
+        let mut updates = worker().boxed();
+
+        assert!(matches!(
+            next(&mut updates).await,
+            WorkspaceUpdate::Snapshot(_)
+        ));
+        assert!(matches!(
+            next(&mut updates).await,
+            WorkspaceUpdate::Event(Event::OverviewOpenedOrClosed { is_open: true })
+        ));
+    }
+
+    #[tokio::test]
+    async fn worker_reconnects_after_the_stream_closes() {
+        let _niri = FakeNiri::start(Script {
+            workspaces: vec![workspace(1, 1, "DP-1")],
+            close_after_replay: true,
+            ..Script::default()
+        });
+        let mut updates = worker().boxed();
+
+        assert!(matches!(
+            next(&mut updates).await,
+            WorkspaceUpdate::Snapshot(_)
+        ));
+        assert!(matches!(
+            next(&mut updates).await,
+            WorkspaceUpdate::Disconnected(_)
+        ));
+        assert!(matches!(
+            next(&mut updates).await,
+            WorkspaceUpdate::Reconnected
+        ));
+        assert!(matches!(
+            next(&mut updates).await,
+            WorkspaceUpdate::Snapshot(_)
+        ));
+    }
+}
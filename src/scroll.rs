@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Turning wheel and touchpad scrolling into workspace steps.
+
+use std::time::{Duration, Instant};
+
+use cosmic::iced::mouse::ScrollDelta;
+
+/// Lines scrolled per step, one wheel notch.
+const LINE_THRESHOLD: f32 = 1.;
+/// Pixels scrolled per step, about a third of a touchpad swipe.
+const PIXEL_THRESHOLD: f32 = 60.;
+/// A pause after which leftover scrolling is dropped, so each swipe starts
+/// from zero.
+const IDLE: Duration = Duration::from_millis(300);
+
+/// Whole steps completed on each axis.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Steps {
+    /// Positive for left, negative for right.
+    pub x: i32,
+    /// Positive for up, negative for down.
+    pub y: i32,
+}
+
+/// Scrolling collected until it adds up to whole steps.
+#[derive(Debug, Default)]
+pub struct ScrollAccumulator {
+    /// Steps collected so far on each axis.
+    x: f32,
+    y: f32,
+    last: Option<Instant>,
+}
+
+impl ScrollAccumulator {
+    /// Adds `delta`, scaled by `sensitivity`, and returns the whole steps it
+    /// completes. Only the dominant axis of each delta counts, so a slightly
+    /// diagonal swipe moves one way.
+    pub fn feed(&mut self, delta: ScrollDelta, sensitivity: f32, now: Instant) -> Steps {
+        let (x, y) = match delta {
+            ScrollDelta::Lines { x, y } => (x / LINE_THRESHOLD, y / LINE_THRESHOLD),
+            ScrollDelta::Pixels { x, y } => (x / PIXEL_THRESHOLD, y / PIXEL_THRESHOLD),
+        };
+        if self
+            .last
+            .is_some_and(|last| now.saturating_duration_since(last) > IDLE)
+        {
+            self.x = 0.;
+            self.y = 0.;
+        }
+        self.last = Some(now);
+        if x.abs() > y.abs() {
+            Steps {
+                x: accumulate(&mut self.x, x * sensitivity),
+                y: 0,
+            }
+        } else {
+            Steps {
+                x: 0,
+                y: accumulate(&mut self.y, y * sensitivity),
+            }
+        }
+    }
+}
+
+/// Adds `delta` to `total`, starting over on a change of direction, and takes
+/// the whole steps out.
+fn accumulate(total: &mut f32, delta: f32) -> i32 {
+    if delta * *total < 0. {
+        *total = 0.;
+    }
+    *total += delta;
+    let steps = total.trunc();
+    *total -= steps;
+    steps as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(deltas: &[ScrollDelta]) -> Vec<i32> {
+        let start = Instant::now();
+        let mut scroll = ScrollAccumulator::default();
+        deltas
+            .iter()
+            .enumerate()
+            .map(|(i, &delta)| {
+                scroll
+                    .feed(delta, 1., start + Duration::from_millis(i as u64 * 10))
+                    .y
+            })
+            .collect()
+    }
+
+    fn pixels(y: f32) -> ScrollDelta {
+        ScrollDelta::Pixels { x: 0., y }
+    }
+
+    fn lines(y: f32) -> ScrollDelta {
+        ScrollDelta::Lines { x: 0., y }
+    }
+
+    #[test]
+    fn wheel_notches_are_one_step_each() {
+        assert_eq!(feed(&[lines(1.), lines(1.), lines(-1.)]), [1, 1, -1]);
+    }
+
+    #[test]
+    fn touchpad_swipes_add_up_to_steps() {
+        let swipe = [pixels(-30.); 5];
+        assert_eq!(feed(&swipe), [0, -1, 0, -1, 0]);
+        assert_eq!(feed(&[pixels(200.)]), [3]);
+    }
+
+    #[test]
+    fn direction_changes_drop_leftover_scrolling() {
+        assert_eq!(
+            feed(&[pixels(50.), pixels(-10.), pixels(50.), pixels(20.)]),
+            [0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn pauses_drop_leftover_scrolling() {
+        let start = Instant::now();
+        let mut scroll = ScrollAccumulator::default();
+        assert_eq!(scroll.feed(pixels(50.), 1., start).y, 0);
+        assert_eq!(scroll.feed(pixels(50.), 1., start + IDLE * 2).y, 0);
+        assert_eq!(scroll.feed(pixels(20.), 1., start + IDLE * 2).y, 1);
+    }
+
+    #[test]
+    fn sensitivity_scales_deltas() {
+        let start = Instant::now();
+        let mut scroll = ScrollAccumulator::default();
+        assert_eq!(scroll.feed(pixels(30.), 2., start).y, 1);
+        assert_eq!(scroll.feed(lines(1.), 0.5, start).y, 0);
+        assert_eq!(scroll.feed(lines(1.), 0.5, start).y, 1);
+    }
+
+    #[test]
+    fn the_dominant_axis_wins() {
+        let start = Instant::now();
+        let mut scroll = ScrollAccumulator::default();
+        let diagonal = ScrollDelta::Pixels { x: -60., y: 30. };
+        assert_eq!(scroll.feed(diagonal, 1., start), Steps { x: -1, y: 0 });
+        let left = ScrollDelta::Lines { x: 1., y: 0. };
+        assert_eq!(scroll.feed(left, 1., start), Steps { x: 1, y: 0 });
+    }
+}
@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Recording and replaying the raw niri event stream, for bug reports.
+//!
+//! Set `NIRI_APPLET_RECORD=<dir>` to write every line read from the event
+//! stream to `<dir>/niri-events-<unix time in ms>-<pid>.jsonl`, one file per
+//! applet process. Start the applet with `NIRI_APPLET_REPLAY=<file>` to feed
+//! such a trace to the applet in place of the live socket, and add
+//! `NIRI_APPLET_REPLAY_REALTIME=1` to keep the original pacing.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use cosmic::iced::futures::{
+    future::{self, BoxFuture},
+    stream::BoxStream,
+    FutureExt, SinkExt, StreamExt,
+};
+use log::{error, info, warn};
+use niri_ipc::Action;
+use serde::{Deserialize, Serialize};
+use tokio::io;
+
+use crate::backend::WorkspaceBackend;
+use crate::niri::{self, NiriState, WorkspaceUpdate};
+
+pub const RECORD_ENV: &str = "NIRI_APPLET_RECORD";
+pub const REPLAY_ENV: &str = "NIRI_APPLET_REPLAY";
+pub const REPLAY_REALTIME_ENV: &str = "NIRI_APPLET_REPLAY_REALTIME";
+
+/// One line of a trace file.
+#[derive(Debug, Serialize, Deserialize)]
+struct TraceLine {
+    /// Milliseconds since the recording started.
+    elapsed_ms: u64,
+    /// The line exactly as niri sent it, without the trailing newline.
+    line: String,
+}
+
+struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+impl Recorder {
+    fn create(dir: &Path) -> io::Result<(Self, PathBuf)> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        std::fs::create_dir_all(dir)?;
+        // cosmic-panel starts one applet per panel at once, each with its own
+        // trace; never share or truncate one.
+        let path = dir.join(format!("niri-events-{}-{}.jsonl", now, std::process::id()));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok((
+            Self {
+                file,
+                started: Instant::now(),
+            },
+            path,
+        ))
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let mut json = serde_json::to_string(&TraceLine {
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            line: line.to_owned(),
+        })?;
+        json.push('\n');
+        self.file.write_all(json.as_bytes())
+    }
+}
+
+static RECORDER: LazyLock<Option<Mutex<Recorder>>> = LazyLock::new(|| {
+    let dir = std::env::var_os(RECORD_ENV)?;
+    match Recorder::create(Path::new(&dir)) {
+        Ok((recorder, path)) => {
+            info!("Recording niri events to {}", path.display());
+            Some(Mutex::new(recorder))
+        }
+        Err(e) => {
+            error!("Failed to start recording niri events: {}", e);
+            None
+        }
+    }
+});
+
+/// Appends a raw event-stream line to the trace, if recording is enabled.
+pub fn record(line: &str) {
+    let Some(recorder) = RECORDER.as_ref() else {
+        return;
+    };
+    if let Err(e) = recorder.lock().unwrap().write(line.trim_end()) {
+        error!("Failed to record niri event: {}", e);
+    }
+}
+
+/// Plays a recorded trace instead of talking to niri.
+pub struct ReplayBackend {
+    path: PathBuf,
+    realtime: bool,
+}
+
+impl ReplayBackend {
+    pub fn from_env() -> Option<Self> {
+        let path = PathBuf::from(std::env::var_os(REPLAY_ENV)?);
+        let realtime = std::env::var(REPLAY_REALTIME_ENV).is_ok_and(|v| v != "0");
+        Some(Self { path, realtime })
+    }
+}
+
+impl WorkspaceBackend for ReplayBackend {
+    fn events(&self) -> BoxStream<'static, WorkspaceUpdate> {
+        let path = self.path.clone();
+        let realtime = self.realtime;
+        cosmic::iced::stream::channel(4, async move |mut output| {
+            let content = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => content,
+                Err(e) => {
+                    error!("Failed to read trace {}: {}", path.display(), e);
+                    let reason = format!("{}: {}", path.display(), e);
+                    let _ = output.send(WorkspaceUpdate::Disconnected(reason)).await;
+                    return;
+                }
+            };
+            info!("Replaying niri events from {}", path.display());
+            // The trace starts with niri's initial `WorkspacesChanged` and
+            // `WindowsChanged`, which fill this in.
+            if output
+                .send(WorkspaceUpdate::Snapshot(NiriState::default()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            let started = Instant::now();
+
+            for (n, raw) in content.lines().enumerate() {
+                let trace: TraceLine = match serde_json::from_str(raw) {
+                    Ok(trace) => trace,
+                    Err(e) => {
+                        warn!("{}:{}: invalid trace line: {}", path.display(), n + 1, e);
+                        continue;
+                    }
+                };
+                if realtime {
+                    let at = started + Duration::from_millis(trace.elapsed_ms);
+                    tokio::time::sleep_until(at.into()).await;
+                }
+                let event = match niri::parse_event(&trace.line) {
+                    Ok(event) => event,
+                    Err(e) => {
+                        warn!("{}:{}: {}", path.display(), n + 1, e);
+                        continue;
+                    }
+                };
+                if output.send(WorkspaceUpdate::Event(event)).await.is_err() {
+                    return;
+                }
+            }
+            info!("Replay finished");
+        })
+        .boxed()
+    }
+
+    fn dispatch(&self, action: Action) -> BoxFuture<'static, Result<(), String>> {
+        info!("Replay mode, not sending {:?}", action);
+        future::ready(Ok(())).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use niri_ipc::Event;
+
+    use super::*;
+    use crate::backend::fake::workspace;
+
+    #[tokio::test]
+    async fn recorded_lines_replay_as_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut recorder, path) = Recorder::create(dir.path()).unwrap();
+        let events = [
+            Event::WorkspacesChanged {
+                workspaces: vec![workspace(1, 1, "DP-1")],
+            },
+            Event::WorkspaceActivated {
+                id: 1,
+                focused: true,
+            },
+        ];
+        for event in &events {
+            recorder
+                .write(&serde_json::to_string(event).unwrap())
+                .unwrap();
+        }
+
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .ends_with(&format!("-{}.jsonl", std::process::id())));
+
+        let replay = ReplayBackend {
+            path,
+            realtime: false,
+        };
+        let updates: Vec<_> = replay.events().collect().await;
+        assert!(matches!(
+            updates.as_slice(),
+            [
+                WorkspaceUpdate::Snapshot(_),
+                WorkspaceUpdate::Event(Event::WorkspacesChanged { .. }),
+                WorkspaceUpdate::Event(Event::WorkspaceActivated { id: 1, .. })
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn unreadable_traces_report_a_disconnect() {
+        let dir = tempfile::tempdir().unwrap();
+        let replay = ReplayBackend {
+            path: dir.path().join("missing.jsonl"),
+            realtime: false,
+        };
+        let updates: Vec<_> = replay.events().collect().await;
+        assert!(matches!(
+            updates.as_slice(),
+            [WorkspaceUpdate::Disconnected(reason)] if reason.contains("missing.jsonl")
+        ));
+    }
+}